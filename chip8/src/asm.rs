@@ -0,0 +1,571 @@
+/*
+ASSEMBLY SYNTAX (for reference):
+
+Mnemonics are the ones printed by `Instruction`'s Display impl and by
+chip8-disasm, and are case insensitive:
+
+    ; comments run to the end of the line
+    SPEED   EQU 2               ; constants, usable anywhere after this line
+            include "font.s"    ; pasted in, relative to the including file
+
+    start:  LD   V0, 0
+    loop:   ADD  V0, SPEED
+            LD   I, sprite
+            DRW  V0, V1, 5
+            JP   loop
+    sprite: DB   0xF0, 0x90, 0x90, 0x90, 0xF0
+            DW   0x1234, start + 2
+
+Numbers are decimal, 0x / # hex or 0b / % binary. Operands may add and
+subtract numbers, constants and labels, and $ is the address of the current
+line. Programs are assembled to start at 0x200.
+*/
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::constants::PROGRAM_START;
+use crate::decode::Instruction;
+
+// deep enough for any sane project, shallow enough to catch include cycles
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file, self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// An assembled program, ready for `Chip8::load_rom`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Assembly {
+    pub bytes: Vec<u8>,
+    // labels and constants, by name
+    pub symbols: BTreeMap<String, u16>,
+}
+
+impl Assembly {
+    /// One `NAME 0xADDR` line per symbol, sorted by name.
+    pub fn symbol_map(&self) -> String {
+        self.symbols
+            .iter()
+            .map(|(name, value)| format!("{} {:#06X}\n", name, value))
+            .collect()
+    }
+}
+
+/// Assembles source text. Includes are resolved relative to the working
+/// directory.
+pub fn assemble(source: &str) -> Result<Assembly, AsmError> {
+    let mut lines = Vec::new();
+    expand(source, "<input>", Path::new("."), 0, &mut lines)?;
+    Assembler::default().run(&lines)
+}
+
+/// Assembles a file. Includes are resolved relative to the including file.
+pub fn assemble_file(path: &Path) -> Result<Assembly, AsmError> {
+    let source = fs::read_to_string(path).map_err(|e| AsmError {
+        file: path.display().to_string(),
+        line: 0,
+        message: e.to_string(),
+    })?;
+    let base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+    let mut lines = Vec::new();
+    expand(&source, &path.display().to_string(), &base, 0, &mut lines)?;
+    Assembler::default().run(&lines)
+}
+
+struct SourceLine {
+    file: String,
+    line: usize,
+    text: String,
+}
+
+impl SourceLine {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: self.file.clone(),
+            line: self.line,
+            message: message.into(),
+        }
+    }
+}
+
+/// Flattens `include` directives into one list of lines, keeping track of
+/// where each line came from for error messages.
+fn expand(
+    source: &str,
+    file: &str,
+    base: &Path,
+    depth: usize,
+    out: &mut Vec<SourceLine>,
+) -> Result<(), AsmError> {
+    for (idx, raw) in source.lines().enumerate() {
+        let line = SourceLine {
+            file: file.to_string(),
+            line: idx + 1,
+            text: strip_comment(raw).trim().to_string(),
+        };
+
+        let mut words = line.text.splitn(2, char::is_whitespace);
+        if !words.next().unwrap_or("").eq_ignore_ascii_case("include") {
+            out.push(line);
+            continue;
+        }
+
+        if depth >= MAX_INCLUDE_DEPTH {
+            return Err(line.error("includes nested too deeply"));
+        }
+        let name = words.next().unwrap_or("").trim().trim_matches('"');
+        if name.is_empty() {
+            return Err(line.error("include needs a file name"));
+        }
+        let path: PathBuf = base.join(name);
+        let included = fs::read_to_string(&path)
+            .map_err(|e| line.error(format!("cannot include {}: {}", path.display(), e)))?;
+        let included_base = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        expand(
+            &included,
+            &path.display().to_string(),
+            &included_base,
+            depth + 1,
+            out,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut in_string = false;
+    for (idx, c) in line.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Splits an operand list on commas that aren't inside a string.
+fn split_operands(text: &str) -> Vec<String> {
+    let mut operands = Vec::new();
+    let mut current = String::new();
+    let mut in_string = false;
+
+    for c in text.chars() {
+        match c {
+            '"' => {
+                in_string = !in_string;
+                current.push(c);
+            }
+            ',' if !in_string => operands.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    if !current.trim().is_empty() || !operands.is_empty() {
+        operands.push(current);
+    }
+
+    operands
+        .into_iter()
+        .map(|op| op.trim().to_string())
+        .collect()
+}
+
+// a line reduced to what it emits, sized on the first pass
+struct Statement<'a> {
+    source: &'a SourceLine,
+    addr: u16,
+    mnemonic: String,
+    operands: Vec<String>,
+}
+
+#[derive(Default)]
+struct Assembler {
+    symbols: BTreeMap<String, u16>,
+}
+
+impl Assembler {
+    fn run(mut self, lines: &[SourceLine]) -> Result<Assembly, AsmError> {
+        // first pass: find every label's address and evaluate constants
+        let mut statements = Vec::new();
+        let mut addr = PROGRAM_START as u32;
+
+        for line in lines {
+            let mut text = line.text.as_str();
+
+            if let Some((label, rest)) = split_label(text) {
+                self.define(line, label, addr)?;
+                text = rest;
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let (mnemonic, operands) = match text.split_once(char::is_whitespace) {
+                Some((mnemonic, rest)) => (mnemonic, split_operands(rest.trim())),
+                None => (text, Vec::new()),
+            };
+
+            // NAME EQU value
+            if let Some(value) = operands
+                .first()
+                .and_then(|op| op.strip_prefix_ignore_case("equ"))
+            {
+                let value = self.eval(line, value.trim(), addr as u16)?;
+                self.define(line, mnemonic, value as u32)?;
+                continue;
+            }
+
+            let mnemonic = mnemonic.to_ascii_uppercase();
+            let size = match mnemonic.as_str() {
+                "DB" => operands.iter().map(|op| data_len(op)).sum(),
+                "DW" => 2 * operands.len() as u32,
+                "LD" if operands.len() == 2 && is_long(&operands[1]) => 4,
+                _ => 2,
+            };
+
+            statements.push(Statement {
+                source: line,
+                addr: addr as u16,
+                mnemonic,
+                operands,
+            });
+
+            addr += size;
+            if addr > 0x10000 {
+                return Err(line.error("program runs past the end of memory"));
+            }
+        }
+
+        // second pass: every symbol is known, emit the bytes
+        let mut bytes = Vec::new();
+        for statement in &statements {
+            self.emit(statement, &mut bytes)?;
+        }
+
+        Ok(Assembly {
+            bytes,
+            symbols: self.symbols,
+        })
+    }
+
+    fn define(&mut self, line: &SourceLine, name: &str, value: u32) -> Result<(), AsmError> {
+        if !is_identifier(name) {
+            return Err(line.error(format!("invalid symbol name '{}'", name)));
+        }
+        if self
+            .symbols
+            .insert(name.to_string(), value as u16)
+            .is_some()
+        {
+            return Err(line.error(format!("'{}' is defined more than once", name)));
+        }
+        Ok(())
+    }
+
+    fn emit(&self, statement: &Statement, bytes: &mut Vec<u8>) -> Result<(), AsmError> {
+        let line = statement.source;
+        let ops = &statement.operands;
+        let addr = statement.addr;
+
+        match statement.mnemonic.as_str() {
+            "DB" => {
+                for op in ops {
+                    if let Some(text) = op.strip_prefix('"').and_then(|op| op.strip_suffix('"')) {
+                        bytes.extend_from_slice(text.as_bytes());
+                    } else {
+                        bytes.push(self.byte(line, op, addr)?);
+                    }
+                }
+            }
+            "DW" => {
+                for op in ops {
+                    let value = self.eval(line, op, addr)?;
+                    if !(-0x8000..=0xFFFF).contains(&value) {
+                        return Err(line.error(format!("{} does not fit in a word", value)));
+                    }
+                    bytes.extend_from_slice(&(value as u16).to_be_bytes());
+                }
+            }
+            _ => {
+                let (instruction, long) = self.instruction(statement)?;
+                bytes.extend_from_slice(&instruction.encode().to_be_bytes());
+                if let Some(word) = long {
+                    bytes.extend_from_slice(&word.to_be_bytes());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn instruction(&self, statement: &Statement) -> Result<(Instruction, Option<u16>), AsmError> {
+        let line = statement.source;
+        let addr = statement.addr;
+        let ops: Vec<Operand> = statement
+            .operands
+            .iter()
+            .map(|op| Operand::parse(op))
+            .collect();
+        let mnemonic = statement.mnemonic.as_str();
+
+        let bad = || {
+            line.error(format!(
+                "invalid operands for {}: '{}'",
+                mnemonic,
+                statement.operands.join(", ")
+            ))
+        };
+        let value = |text: &str, max: i64| -> Result<u16, AsmError> {
+            let value = self.eval(line, text, addr)?;
+            if value < 0 || value > max {
+                return Err(line.error(format!("{} is out of range (0 to {:#X})", value, max)));
+            }
+            Ok(value as u16)
+        };
+        let byte = |text: &str| self.byte(line, text, addr);
+
+        use Operand::*;
+        let instruction = match (mnemonic, ops.as_slice()) {
+            ("CLS", []) => Instruction::Clear,
+            ("RET", []) => Instruction::Return,
+            ("SCD", [Expr(n)]) => Instruction::ScrollDown(value(n, 0xF)? as u8),
+            ("SCU", [Expr(n)]) => Instruction::ScrollUp(value(n, 0xF)? as u8),
+            ("SCR", []) => Instruction::ScrollRight,
+            ("SCL", []) => Instruction::ScrollLeft,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::Lores,
+            ("HIGH", []) => Instruction::Hires,
+            ("JP", [Expr(a)]) => Instruction::Jump(value(a, 0xFFF)?),
+            ("JP", [Reg(x), Expr(a)]) => Instruction::JumpOffset {
+                x: *x,
+                nnn: value(a, 0xFFF)?,
+            },
+            ("CALL", [Expr(a)]) => Instruction::Call(value(a, 0xFFF)?),
+            ("SE", [Reg(x), Reg(y)]) => Instruction::SkipEqReg { x: *x, y: *y },
+            ("SE", [Reg(x), Expr(nn)]) => Instruction::SkipEqImm {
+                x: *x,
+                nn: byte(nn)?,
+            },
+            ("SNE", [Reg(x), Reg(y)]) => Instruction::SkipNeReg { x: *x, y: *y },
+            ("SNE", [Reg(x), Expr(nn)]) => Instruction::SkipNeImm {
+                x: *x,
+                nn: byte(nn)?,
+            },
+            ("SAVE", [Reg(x), Reg(y)]) => Instruction::SaveRange { x: *x, y: *y },
+            ("LOAD", [Reg(x), Reg(y)]) => Instruction::LoadRange { x: *x, y: *y },
+            ("LD", [Reg(x), Reg(y)]) => Instruction::Move { x: *x, y: *y },
+            ("LD", [Reg(x), Keyword("DT")]) => Instruction::GetDelay { x: *x },
+            ("LD", [Reg(x), Keyword("K")]) => Instruction::WaitKey { x: *x },
+            ("LD", [Reg(x), Keyword("[I]")]) => Instruction::Load { x: *x },
+            ("LD", [Reg(x), Keyword("R")]) => Instruction::LoadFlags { x: *x },
+            ("LD", [Reg(x), Expr(nn)]) => Instruction::LoadImm {
+                x: *x,
+                nn: byte(nn)?,
+            },
+            ("LD", [Keyword("I"), Long(a)]) => {
+                let word = value(a, 0xFFFF)?;
+                return Ok((Instruction::LoadLongI, Some(word)));
+            }
+            ("LD", [Keyword("I"), Expr(a)]) => Instruction::LoadI(value(a, 0xFFF)?),
+            ("LD", [Keyword("DT"), Reg(x)]) => Instruction::SetDelay { x: *x },
+            ("LD", [Keyword("ST"), Reg(x)]) => Instruction::SetSound { x: *x },
+            ("LD", [Keyword("F"), Reg(x)]) => Instruction::Font { x: *x },
+            ("LD", [Keyword("HF"), Reg(x)]) => Instruction::BigFont { x: *x },
+            ("LD", [Keyword("B"), Reg(x)]) => Instruction::Bcd { x: *x },
+            ("LD", [Keyword("[I]"), Reg(x)]) => Instruction::Store { x: *x },
+            ("LD", [Keyword("R"), Reg(x)]) => Instruction::SaveFlags { x: *x },
+            ("ADD", [Keyword("I"), Reg(x)]) => Instruction::AddI { x: *x },
+            ("ADD", [Reg(x), Reg(y)]) => Instruction::Add { x: *x, y: *y },
+            ("ADD", [Reg(x), Expr(nn)]) => Instruction::AddImm {
+                x: *x,
+                nn: byte(nn)?,
+            },
+            ("OR", [Reg(x), Reg(y)]) => Instruction::Or { x: *x, y: *y },
+            ("AND", [Reg(x), Reg(y)]) => Instruction::And { x: *x, y: *y },
+            ("XOR", [Reg(x), Reg(y)]) => Instruction::Xor { x: *x, y: *y },
+            ("SUB", [Reg(x), Reg(y)]) => Instruction::Sub { x: *x, y: *y },
+            ("SUBN", [Reg(x), Reg(y)]) => Instruction::SubN { x: *x, y: *y },
+            ("SHR", [Reg(x)]) => Instruction::ShiftRight { x: *x, y: *x },
+            ("SHR", [Reg(x), Reg(y)]) => Instruction::ShiftRight { x: *x, y: *y },
+            ("SHL", [Reg(x)]) => Instruction::ShiftLeft { x: *x, y: *x },
+            ("SHL", [Reg(x), Reg(y)]) => Instruction::ShiftLeft { x: *x, y: *y },
+            ("RND", [Reg(x), Expr(nn)]) => Instruction::Random {
+                x: *x,
+                nn: byte(nn)?,
+            },
+            ("DRW", [Reg(x), Reg(y), Expr(n)]) => Instruction::Draw {
+                x: *x,
+                y: *y,
+                n: value(n, 0xF)? as u8,
+            },
+            ("SKP", [Reg(x)]) => Instruction::SkipKey { x: *x },
+            ("SKNP", [Reg(x)]) => Instruction::SkipNotKey { x: *x },
+            ("PLANE", [Expr(n)]) => Instruction::Planes(value(n, 0x3)? as u8),
+            ("AUDIO", []) => Instruction::LoadAudio,
+            ("PITCH", [Reg(x)]) => Instruction::Pitch { x: *x },
+            (
+                "CLS" | "RET" | "SCD" | "SCU" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "JP"
+                | "CALL" | "SE" | "SNE" | "SAVE" | "LOAD" | "LD" | "ADD" | "OR" | "AND" | "XOR"
+                | "SUB" | "SUBN" | "SHR" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP" | "PLANE"
+                | "AUDIO" | "PITCH",
+                _,
+            ) => return Err(bad()),
+            _ => return Err(line.error(format!("unknown mnemonic '{}'", mnemonic))),
+        };
+
+        Ok((instruction, None))
+    }
+
+    fn byte(&self, line: &SourceLine, text: &str, addr: u16) -> Result<u8, AsmError> {
+        let value = self.eval(line, text, addr)?;
+        if !(-0x80..=0xFF).contains(&value) {
+            return Err(line.error(format!("{} does not fit in a byte", value)));
+        }
+        Ok(value as u8)
+    }
+
+    /// Evaluates `term (+|- term)*`, where a term is a number, a symbol or $.
+    fn eval(&self, line: &SourceLine, text: &str, addr: u16) -> Result<i64, AsmError> {
+        let mut total: i64 = 0;
+        let mut sign = 1;
+        let mut term = String::new();
+
+        let mut terms = Vec::new();
+        for c in text.chars().chain(std::iter::once('+')) {
+            match c {
+                '+' | '-' if !term.trim().is_empty() => {
+                    terms.push((sign, std::mem::take(&mut term)));
+                    sign = if c == '-' { -1 } else { 1 };
+                }
+                // a leading sign
+                '-' => sign = -sign,
+                '+' => {}
+                _ => term.push(c),
+            }
+        }
+        if terms.is_empty() {
+            return Err(line.error("expected a value"));
+        }
+
+        for (sign, term) in terms {
+            let term = term.trim();
+            let value = if term == "$" {
+                addr as i64
+            } else if let Some(value) = parse_number(term) {
+                value
+            } else if let Some(value) = self.symbols.get(term) {
+                *value as i64
+            } else if is_identifier(term) {
+                return Err(line.error(format!("undefined symbol '{}'", term)));
+            } else {
+                return Err(line.error(format!("invalid value '{}'", term)));
+            };
+            total += sign * value;
+        }
+
+        Ok(total)
+    }
+}
+
+enum Operand<'a> {
+    Reg(u8),
+    // I, DT, ST, K, F, HF, B, R and [I], upper cased
+    Keyword(&'static str),
+    // LONG addr, the operand of the XO-CHIP F000 NNNN
+    Long(&'a str),
+    Expr(&'a str),
+}
+
+impl<'a> Operand<'a> {
+    fn parse(text: &'a str) -> Self {
+        const KEYWORDS: [&str; 9] = ["I", "DT", "ST", "K", "F", "HF", "B", "R", "[I]"];
+
+        let upper = text.to_ascii_uppercase();
+        if let Some(keyword) = KEYWORDS.iter().find(|keyword| **keyword == upper) {
+            return Operand::Keyword(keyword);
+        }
+        if upper.len() == 2 && upper.starts_with('V') {
+            if let Some(x) = upper[1..].chars().next().and_then(|c| c.to_digit(16)) {
+                return Operand::Reg(x as u8);
+            }
+        }
+        if let Some(rest) = text.strip_prefix_ignore_case("long") {
+            return Operand::Long(rest.trim());
+        }
+        Operand::Expr(text)
+    }
+}
+
+trait StripPrefixIgnoreCase {
+    fn strip_prefix_ignore_case(&self, prefix: &str) -> Option<&str>;
+}
+
+impl StripPrefixIgnoreCase for str {
+    /// Strips a keyword prefix, which has to be followed by whitespace.
+    fn strip_prefix_ignore_case(&self, prefix: &str) -> Option<&str> {
+        let head = self.get(..prefix.len())?;
+        let rest = &self[prefix.len()..];
+        if head.eq_ignore_ascii_case(prefix) && rest.starts_with(char::is_whitespace) {
+            Some(rest)
+        } else {
+            None
+        }
+    }
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (label, rest) = text.split_once(':')?;
+    let label = label.trim();
+    if is_identifier(label) {
+        Some((label, rest.trim()))
+    } else {
+        None
+    }
+}
+
+fn is_long(operand: &str) -> bool {
+    operand.strip_prefix_ignore_case("long").is_some()
+}
+
+fn data_len(operand: &str) -> u32 {
+    match operand
+        .strip_prefix('"')
+        .and_then(|op| op.strip_suffix('"'))
+    {
+        Some(text) => text.len() as u32,
+        None => 1,
+    }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub(crate) fn parse_number(text: &str) -> Option<i64> {
+    let lower = text.to_ascii_lowercase();
+    if let Some(hex) = lower.strip_prefix("0x").or_else(|| lower.strip_prefix('#')) {
+        i64::from_str_radix(hex, 16).ok()
+    } else if let Some(bin) = lower.strip_prefix("0b").or_else(|| lower.strip_prefix('%')) {
+        i64::from_str_radix(bin, 2).ok()
+    } else {
+        lower.parse().ok()
+    }
+}
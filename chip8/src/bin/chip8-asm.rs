@@ -0,0 +1,59 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use chip8::asm::assemble_file;
+
+const USAGE: &str = "Usage: chip8-asm <source> [-o <rom>] [--symbols <file>]";
+
+fn main() {
+    let mut source = None;
+    let mut output = None;
+    let mut symbols = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => output = args.next().map(PathBuf::from),
+            "--symbols" => symbols = args.next().map(PathBuf::from),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if source.is_none() && !arg.starts_with('-') => source = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("Unexpected argument '{}'\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    let Some(source) = source else {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    };
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+
+    let assembly = assemble_file(&source).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    });
+
+    if let Err(e) = fs::write(&output, &assembly.bytes) {
+        eprintln!("Could not write {}: {}", output.display(), e);
+        process::exit(1);
+    }
+    if let Some(symbols) = symbols {
+        if let Err(e) = fs::write(&symbols, assembly.symbol_map()) {
+            eprintln!("Could not write {}: {}", symbols.display(), e);
+            process::exit(1);
+        }
+    }
+
+    println!(
+        "{} bytes written to {}",
+        assembly.bytes.len(),
+        output.display()
+    );
+}
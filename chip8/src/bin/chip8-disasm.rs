@@ -0,0 +1,57 @@
+use std::env;
+use std::fs;
+use std::process;
+
+use chip8::constants::PROGRAM_START;
+use chip8::disasm::{disassemble, disassemble_linear, Syntax};
+
+const USAGE: &str = "Usage: chip8-disasm <rom> [--syntax cowgod|octo] [--linear]";
+
+fn main() {
+    let mut rom_path = None;
+    let mut syntax = Syntax::Cowgod;
+    let mut linear = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--syntax" => {
+                let name = args.next().unwrap_or_default();
+                syntax = Syntax::from_name(&name).unwrap_or_else(|| {
+                    eprintln!("Unknown syntax '{}'\n{}", name, USAGE);
+                    process::exit(2);
+                });
+            }
+            "--linear" => linear = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if rom_path.is_none() && !arg.starts_with("--") => rom_path = Some(arg),
+            _ => {
+                eprintln!("Unexpected argument '{}'\n{}", arg, USAGE);
+                process::exit(2);
+            }
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    };
+
+    let rom = fs::read(&rom_path).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", rom_path, e);
+        process::exit(1);
+    });
+
+    let lines = if linear {
+        disassemble_linear(&rom, PROGRAM_START as u16)
+    } else {
+        disassemble(&rom, PROGRAM_START as u16)
+    };
+
+    for line in lines {
+        println!("{}", line.format(syntax));
+    }
+}
@@ -0,0 +1,709 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, BufWriter, Write};
+use std::process;
+
+use chip8::chip8::Chip8;
+use chip8::constants::MAX_FONT_BASE;
+use chip8::debugger::{parse_address, Condition, Debugger, StopReason, Watch};
+use chip8::disasm::{disassemble_linear, Syntax};
+use chip8::dump::{display_ascii, display_pbm, state_json};
+use chip8::mode::Mode;
+use chip8::movie::{Movie, MoviePlayer, MovieRecorder};
+use chip8::quirks::Quirks;
+use chip8::scheduler::{Scheduler, FRAME_RATE};
+use chip8::trace::{BinaryTracer, TextTracer, TraceFilter};
+
+const USAGE: &str = "Usage: chip8-headless <rom> [options]
+
+Options:
+  --frames <n>          run for n frames of 60 Hz (default 60)
+  --cycles <n>          run for n instructions instead
+  --ipf <n>             instructions per frame (default 10)
+  --quirks <preset>     default, vip, chip48 or schip
+  --mode <mode>         classic or xochip
+  --seed <n>            seed for CXNN, for reproducible runs
+  --font <file>         custom font, 80 bytes of 4x5 glyphs optionally
+                        followed by 160 bytes of 8x10 ones
+  --font-base <addr>    where the font goes in memory (default 0x050)
+  --keys <script>       key events, e.g. \"10:5+,20:5-\" presses key 5 on
+                        frame 10 and releases it on frame 20
+  --keys-file <file>    the same events, separated by whitespace
+  --display <format>    ascii, pbm or none (default ascii)
+  --display-out <file>  write the display there instead of stdout
+  --state <file>        dump registers and memory as JSON (- for stdout)
+  --trace <file>        log every instruction executed
+  --trace-format <fmt>  text or binary (default text)
+  --trace-addrs <a-b>   only trace instructions at these addresses
+  --trace-cycles <a-b>  only trace these cycles, counting from 0, end
+                        exclusive
+  --record <file>       record the keys pressed into a movie
+  --play <file>         play a movie back to its end, with the settings
+                        it was recorded with. Exits with 1 if the run
+                        drifts from the recording
+  --debug               start paused in an interactive debugger, type help
+                        at the prompt for its commands";
+
+const DEBUG_HELP: &str = "Commands:
+  c, continue [frames]      run until something stops execution
+  s, step [n]               run n instructions (default 1)
+  n, next                   step, running calls through to their return
+  finish                    run until the current subroutine returns
+  b, break <addr> [if <condition>]
+                            e.g. break 0x2A4 if V3 == 0x10
+  d, delete <addr>          remove a breakpoint
+  w, watch <Vx|addr>        stop when a register or RAM byte changes
+  unwatch <Vx|addr>         remove a watch
+  l, list                   breakpoints and watches
+  r, regs                   registers and timers
+  x <addr> [n]              n bytes of RAM (default 16)
+  dis [addr] [n]            disassemble n instructions (default 8 at PC)
+  display                   the screen
+  key <k> <down|up>         press or release a key
+  q, quit                   stop debugging and write the usual output
+Conditions compare Vx, I, PC, SP, DT, ST, [addr] or numbers with
+==, !=, <, <=, > or >=.";
+
+struct KeyEvent {
+    frame: u64,
+    key: usize,
+    pressed: bool,
+}
+
+struct Options {
+    rom: String,
+    frames: u64,
+    cycles: Option<u64>,
+    ipf: u64,
+    quirks: Quirks,
+    mode: Mode,
+    seed: Option<u64>,
+    font: Option<String>,
+    font_base: Option<u16>,
+    keys: Vec<KeyEvent>,
+    display: String,
+    display_out: Option<String>,
+    state: Option<String>,
+    debug: bool,
+    record: Option<String>,
+    play: Option<String>,
+    // set by a movie, otherwise ipf frames a second
+    ips: Option<u64>,
+    trace: Option<String>,
+    trace_binary: bool,
+    trace_filter: TraceFilter,
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn parse_number(flag: &str, value: Option<String>) -> u64 {
+    value
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| fail(&format!("{} needs a number", flag)))
+}
+
+// "a-b" with either end decimal or 0x hex
+fn parse_range(flag: &str, value: Option<String>) -> (u64, u64) {
+    let number = |text: &str| match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    };
+    value
+        .as_deref()
+        .and_then(|value| value.split_once('-'))
+        .and_then(|(start, end)| Some((number(start)?, number(end)?)))
+        .unwrap_or_else(|| fail(&format!("{} needs a range like 0x200-0x2FF", flag)))
+}
+
+fn parse_keys(script: &str) -> Vec<KeyEvent> {
+    script
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|token| !token.is_empty())
+        .map(|token| {
+            let parsed = token.split_once(':').and_then(|(frame, key)| {
+                let pressed = match key.chars().last()? {
+                    '+' => true,
+                    '-' => false,
+                    _ => return None,
+                };
+                Some(KeyEvent {
+                    frame: frame.parse().ok()?,
+                    key: usize::from_str_radix(&key[..key.len() - 1], 16).ok()?,
+                    pressed,
+                })
+            });
+            parsed.unwrap_or_else(|| fail(&format!("Invalid key event '{}'", token)))
+        })
+        .collect()
+}
+
+fn parse_args() -> Options {
+    let mut options = Options {
+        rom: String::new(),
+        frames: 60,
+        cycles: None,
+        ipf: 10,
+        quirks: Quirks::default(),
+        mode: Mode::Classic,
+        seed: None,
+        font: None,
+        font_base: None,
+        keys: Vec::new(),
+        display: "ascii".to_string(),
+        display_out: None,
+        state: None,
+        debug: false,
+        record: None,
+        play: None,
+        ips: None,
+        trace: None,
+        trace_binary: false,
+        trace_filter: TraceFilter::default(),
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => options.frames = parse_number(&arg, args.next()),
+            "--cycles" => options.cycles = Some(parse_number(&arg, args.next())),
+            "--ipf" => options.ipf = parse_number(&arg, args.next()).max(1),
+            "--quirks" => {
+                let name = args.next().unwrap_or_default();
+                options.quirks = Quirks::from_name(&name)
+                    .unwrap_or_else(|| fail(&format!("Unknown quirks preset '{}'", name)));
+            }
+            "--mode" => {
+                let name = args.next().unwrap_or_default();
+                options.mode = Mode::from_name(&name)
+                    .unwrap_or_else(|| fail(&format!("Unknown mode '{}'", name)));
+            }
+            "--seed" => options.seed = Some(parse_number(&arg, args.next())),
+            "--font" => options.font = args.next(),
+            "--font-base" => {
+                let addr = args.next().as_deref().and_then(parse_address);
+                match addr {
+                    Some(addr) if addr <= MAX_FONT_BASE => options.font_base = Some(addr),
+                    _ => fail(&format!(
+                        "--font-base needs an address up to {:#05x}",
+                        MAX_FONT_BASE
+                    )),
+                }
+            }
+            "--keys" => options
+                .keys
+                .extend(parse_keys(&args.next().unwrap_or_default())),
+            "--keys-file" => {
+                let path = args.next().unwrap_or_default();
+                let script = fs::read_to_string(&path)
+                    .unwrap_or_else(|e| fail(&format!("Could not read {}: {}", path, e)));
+                let script: Vec<&str> = script
+                    .lines()
+                    .map(|line| line.split('#').next().unwrap_or(""))
+                    .collect();
+                options.keys.extend(parse_keys(&script.join(" ")));
+            }
+            "--display" => options.display = args.next().unwrap_or_default(),
+            "--display-out" => options.display_out = args.next(),
+            "--state" => options.state = args.next(),
+            "--debug" => options.debug = true,
+            "--record" => options.record = args.next(),
+            "--play" => options.play = args.next(),
+            "--trace" => options.trace = args.next(),
+            "--trace-format" => {
+                options.trace_binary = match args.next().as_deref() {
+                    Some("text") => false,
+                    Some("binary") => true,
+                    _ => fail("--trace-format needs text or binary"),
+                }
+            }
+            "--trace-addrs" => {
+                let (start, end) = parse_range(&arg, args.next());
+                if start > 0xFFFF || end > 0xFFFF {
+                    fail("--trace-addrs needs addresses up to 0xFFFF");
+                }
+                options.trace_filter.addrs = Some(start as u16..=end as u16);
+            }
+            "--trace-cycles" => {
+                let (start, end) = parse_range(&arg, args.next());
+                options.trace_filter.cycles = Some(start..end);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if options.rom.is_empty() && !arg.starts_with("--") => options.rom = arg,
+            _ => fail(&format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    if options.rom.is_empty() {
+        fail("No ROM given");
+    }
+    if !["ascii", "pbm", "none"].contains(&options.display.as_str()) {
+        fail(&format!("Unknown display format '{}'", options.display));
+    }
+    if options.debug && (options.record.is_some() || options.play.is_some()) {
+        fail("--debug can't be combined with --record or --play");
+    }
+    // a movie is whole frames, --cycles can stop part way through one
+    if options.cycles.is_some() && (options.record.is_some() || options.play.is_some()) {
+        fail("--cycles can't be combined with --record or --play, use --frames");
+    }
+    if options.play.is_some() && !options.keys.is_empty() {
+        fail("A movie brings its own keys, --keys can't be added to it");
+    }
+    options.keys.sort_by_key(|event| event.frame);
+    options
+}
+
+fn write_output(path: Option<&str>, contents: &str) {
+    match path {
+        None | Some("-") => print!("{}", contents),
+        Some(path) => {
+            if let Err(e) = fs::write(path, contents) {
+                eprintln!("Could not write {}: {}", path, e);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+/// Runs the ROM for the requested frames or cycles, recording or playing a
+/// movie alongside. Returns the exit status.
+fn run(
+    chip8: &mut Chip8,
+    options: &Options,
+    mut recorder: Option<&mut MovieRecorder>,
+    mut player: Option<&mut MoviePlayer>,
+) -> i32 {
+    let mut status = 0;
+    let mut cycles = 0;
+    let mut frame = 0;
+    let mut keys = options.keys.iter().peekable();
+    let mut scheduler = Scheduler::new(options.ips.unwrap_or(options.ipf * FRAME_RATE));
+
+    'frames: while options.cycles.is_some() || frame < options.frames {
+        while let Some(event) = keys.next_if(|event| event.frame <= frame) {
+            let pressed = match recorder.as_deref_mut() {
+                Some(recorder) => recorder.keypress(chip8, event.key, event.pressed),
+                None => chip8.keypress(event.key, event.pressed),
+            };
+            if let Err(e) = pressed {
+                eprintln!("Frame {}: {}", event.frame, e);
+                status = 1;
+                break 'frames;
+            }
+        }
+        if let Some(Err(e)) = player
+            .as_deref_mut()
+            .map(|player| player.begin_frame(chip8))
+        {
+            eprintln!("Frame {}: {}", frame, e);
+            status = 1;
+            break 'frames;
+        }
+
+        for _ in 0..scheduler.next_frame_cycles() {
+            if options.cycles.is_some_and(|limit| cycles >= limit) {
+                break 'frames;
+            }
+            // the frame an EXIT lands in still finishes, so a movie ends on
+            // the same state playback gets to
+            if chip8.exited {
+                break;
+            }
+            if let Err(e) = chip8.tick() {
+                eprintln!("Emulation halted: {}", e);
+                status = 1;
+                break 'frames;
+            }
+            cycles += 1;
+        }
+
+        chip8.tick_timers();
+        frame += 1;
+        if let Some(recorder) = recorder.as_deref_mut() {
+            recorder.end_frame(chip8);
+        }
+        if let Some(Err(e)) = player.as_deref_mut().map(|player| player.end_frame(chip8)) {
+            eprintln!("{}", e);
+            status = 1;
+            break 'frames;
+        }
+        if chip8.exited {
+            break;
+        }
+    }
+
+    status
+}
+
+// the debugger's view of the run: where in the frame it is, so timers and
+// scripted keys keep their usual pace while stepping
+struct Session<'a> {
+    chip8: &'a mut Chip8,
+    debugger: Debugger,
+    options: &'a Options,
+    next_key: usize,
+    frame: u64,
+    cycle: u64,
+    faulted: bool,
+}
+
+impl Session<'_> {
+    fn advance(&mut self) -> Option<StopReason> {
+        if self.cycle == 0 {
+            while let Some(event) = self
+                .options
+                .keys
+                .get(self.next_key)
+                .filter(|event| event.frame <= self.frame)
+            {
+                self.next_key += 1;
+                if let Err(e) = self.chip8.keypress(event.key, event.pressed) {
+                    println!("Frame {}: {}", event.frame, e);
+                }
+            }
+        }
+
+        let reason = self.debugger.tick(self.chip8);
+        if matches!(reason, Some(StopReason::Error(_))) {
+            self.faulted = true;
+        }
+        if !matches!(reason, Some(StopReason::Breakpoint { .. })) {
+            self.cycle += 1;
+            if self.cycle == self.options.ipf {
+                self.chip8.tick_timers();
+                self.frame += 1;
+                self.cycle = 0;
+            }
+        }
+        reason
+    }
+
+    fn run_until_stop(&mut self, frame_limit: Option<u64>) -> Option<StopReason> {
+        let last_frame = frame_limit.map(|frames| self.frame + frames);
+        while !self.debugger.is_paused() {
+            if let Some(reason) = self.advance() {
+                return Some(reason);
+            }
+            if last_frame.is_some_and(|last| self.frame >= last) {
+                self.debugger.pause();
+            }
+        }
+        None
+    }
+
+    fn location(&self) -> String {
+        let pc = self.chip8.program_counter as usize;
+        let end = (pc + 4).min(self.chip8.ram.len());
+        disassemble_linear(&self.chip8.ram[pc.min(end)..end], pc as u16)
+            .first()
+            .map(|line| line.format(Syntax::Cowgod))
+            .unwrap_or_else(|| format!("{:#05X}  out of memory", pc))
+    }
+
+    fn registers(&self) -> String {
+        let chip8 = &self.chip8;
+        let vregs: Vec<String> = chip8
+            .vregs
+            .iter()
+            .enumerate()
+            .map(|(x, value)| format!("V{:X} {:02X}", x, value))
+            .collect();
+        format!(
+            "PC {:#05X}  I {:#05X}  SP {}  DT {}  ST {}  frame {}\n{}\n{}",
+            chip8.program_counter,
+            chip8.ireg,
+            chip8.stack_pointer,
+            chip8.delay_timer,
+            chip8.sound_timer,
+            self.frame,
+            vregs[..8].join("  "),
+            vregs[8..].join("  "),
+        )
+    }
+
+    /// Runs one command line. Returns false once the user quits.
+    fn command(&mut self, line: &str) -> Result<bool, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let rest: Vec<&str> = words.collect();
+        let address = |text: Option<&&str>| {
+            text.and_then(|text| parse_address(text))
+                .ok_or_else(|| "expected an address".to_string())
+        };
+        let count = |text: Option<&&str>, default: u64| match text {
+            Some(text) => text
+                .parse::<u64>()
+                .map_err(|_| format!("invalid count '{}'", text)),
+            None => Ok(default),
+        };
+
+        let mut stopped = None;
+        match command {
+            "c" | "continue" => {
+                let limit = rest.first().map(|_| count(rest.first(), 0)).transpose()?;
+                self.debugger.resume();
+                stopped = Some(self.run_until_stop(limit));
+            }
+            "s" | "step" => {
+                let mut reason = None;
+                for _ in 0..count(rest.first(), 1)? {
+                    self.debugger.step();
+                    reason = self.run_until_stop(None);
+                    if reason != Some(StopReason::Step) {
+                        break;
+                    }
+                }
+                stopped = Some(reason);
+            }
+            "n" | "next" => {
+                self.debugger.step_over(self.chip8);
+                stopped = Some(self.run_until_stop(None));
+            }
+            "finish" => {
+                self.debugger.step_out(self.chip8);
+                stopped = Some(self.run_until_stop(None));
+            }
+            "b" | "break" => {
+                let addr = address(rest.first())?;
+                let condition = match rest.get(1) {
+                    Some(&"if") => Some(Condition::parse(&rest[2..].join(" "))?),
+                    Some(other) => return Err(format!("expected 'if', found '{}'", other)),
+                    None => None,
+                };
+                self.debugger.add_breakpoint(addr, condition);
+            }
+            "d" | "delete" => {
+                let addr = address(rest.first())?;
+                if !self.debugger.remove_breakpoint(addr) {
+                    return Err(format!("no breakpoint at {:#05X}", addr));
+                }
+            }
+            "w" | "watch" | "unwatch" => {
+                let watch = rest
+                    .first()
+                    .and_then(|text| Watch::parse(text))
+                    .ok_or("expected a register or an address")?;
+                if command == "unwatch" {
+                    if !self.debugger.remove_watch(watch) {
+                        return Err(format!("{} is not watched", watch));
+                    }
+                } else {
+                    self.debugger.add_watch(watch);
+                }
+            }
+            "l" | "list" => {
+                for (addr, condition) in self.debugger.breakpoints() {
+                    match condition {
+                        Some(condition) => println!("break {:#05X} if {}", addr, condition),
+                        None => println!("break {:#05X}", addr),
+                    }
+                }
+                for watch in self.debugger.watches() {
+                    println!("watch {}", watch);
+                }
+            }
+            "r" | "regs" => println!("{}", self.registers()),
+            "x" => {
+                let addr = address(rest.first())? as usize;
+                let end = addr
+                    .saturating_add(count(rest.get(1), 16)? as usize)
+                    .min(self.chip8.ram.len());
+                for (row, bytes) in self.chip8.ram[addr.min(end)..end].chunks(16).enumerate() {
+                    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+                    println!("{:#05X}  {}", addr + row * 16, hex.join(" "));
+                }
+            }
+            "dis" => {
+                let addr = match rest.first() {
+                    Some(_) => address(rest.first())?,
+                    None => self.chip8.program_counter,
+                } as usize;
+                let n = count(rest.get(1), 8)? as usize;
+                let end = addr
+                    .saturating_add(n.saturating_mul(4))
+                    .min(self.chip8.ram.len());
+                let lines = disassemble_linear(&self.chip8.ram[addr.min(end)..end], addr as u16);
+                for line in lines.iter().take(n) {
+                    let marker = if line.addr() == self.chip8.program_counter {
+                        "=>"
+                    } else {
+                        "  "
+                    };
+                    println!("{} {}", marker, line.format(Syntax::Cowgod));
+                }
+            }
+            "display" => print!("{}", display_ascii(self.chip8)),
+            "key" => {
+                let key = rest
+                    .first()
+                    .and_then(|text| usize::from_str_radix(text, 16).ok())
+                    .ok_or("expected a key 0-F")?;
+                let pressed = match rest.get(1) {
+                    Some(&"down") => true,
+                    Some(&"up") => false,
+                    _ => return Err("expected down or up".to_string()),
+                };
+                self.chip8
+                    .keypress(key, pressed)
+                    .map_err(|e| e.to_string())?;
+            }
+            "q" | "quit" => return Ok(false),
+            "h" | "help" => println!("{}", DEBUG_HELP),
+            _ => return Err(format!("unknown command '{}', try help", command)),
+        }
+
+        if let Some(reason) = stopped {
+            if let Some(reason) = reason.filter(|reason| *reason != StopReason::Step) {
+                println!("Stopped: {}", reason);
+            }
+            println!("{}", self.location());
+        }
+        Ok(true)
+    }
+}
+
+/// Reads debugger commands from stdin until quit or end of input. Returns
+/// the exit status.
+fn debug(chip8: &mut Chip8, options: &Options) -> i32 {
+    let mut session = Session {
+        chip8,
+        debugger: Debugger::new(),
+        options,
+        next_key: 0,
+        frame: 0,
+        cycle: 0,
+        faulted: false,
+    };
+    session.debugger.pause();
+    println!("{}", session.location());
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("(chip8) ");
+        io::stdout().flush().ok();
+        let Some(Ok(line)) = lines.next() else {
+            println!();
+            break;
+        };
+        match session.command(&line) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    session.faulted as i32
+}
+
+fn main() {
+    let mut options = parse_args();
+
+    let rom = fs::read(&options.rom).unwrap_or_else(|e| {
+        eprintln!("Could not read {}: {}", options.rom, e);
+        process::exit(1);
+    });
+
+    // the movie decides how the machine is set up and how long it runs
+    let mut player = options.play.as_deref().map(|path| {
+        let movie = fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| Movie::from_bytes(&data).map_err(|e| e.to_string()))
+            .and_then(|movie| {
+                movie
+                    .check_rom(&rom)
+                    .map(|_| movie)
+                    .map_err(|e| e.to_string())
+            })
+            .unwrap_or_else(|e| {
+                eprintln!("Could not play {}: {}", path, e);
+                process::exit(1);
+            });
+        let header = &movie.header;
+        options.quirks = header.quirks;
+        options.mode = header.mode;
+        options.seed = Some(header.seed);
+        options.font_base = Some(header.font_base);
+        options.ips = Some(header.ips);
+        options.frames = movie.frames as u64;
+        MoviePlayer::new(movie)
+    });
+
+    let mut chip8 = Chip8::new(options.quirks).with_mode(options.mode);
+    if let Some(seed) = options.seed {
+        chip8 = chip8.with_seed(seed);
+    }
+    if let Some(addr) = options.font_base {
+        chip8 = chip8.try_with_font_base(addr).unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1);
+        });
+    }
+    chip8.load_fonts();
+    if let Some(path) = options.font.as_deref() {
+        let loaded = fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|font| chip8.load_custom_font(&font).map_err(|e| e.to_string()));
+        if let Err(e) = loaded {
+            eprintln!("Could not load font {}: {}", path, e);
+            process::exit(1);
+        }
+    }
+    if let Err(e) = chip8.load_rom(&rom) {
+        eprintln!("Could not load ROM: {}", e);
+        process::exit(1);
+    }
+
+    if let Some(path) = options.trace.as_deref() {
+        let file = fs::File::create(path).unwrap_or_else(|e| {
+            eprintln!("Could not create {}: {}", path, e);
+            process::exit(1);
+        });
+        let filter = options.trace_filter.clone();
+        chip8 = if options.trace_binary {
+            chip8.with_tracer(BinaryTracer::new(BufWriter::new(file), filter))
+        } else {
+            chip8.with_tracer(TextTracer::new(BufWriter::new(file), filter))
+        };
+    }
+
+    let mut recorder = options
+        .record
+        .as_ref()
+        .map(|_| MovieRecorder::new(&chip8, &rom, options.ipf * FRAME_RATE));
+
+    let mut status = if options.debug {
+        debug(&mut chip8, &options)
+    } else {
+        run(&mut chip8, &options, recorder.as_mut(), player.as_mut())
+    };
+    if let (Some(recorder), Some(path)) = (recorder, options.record.as_deref()) {
+        if let Err(e) = fs::write(path, recorder.finish(&chip8).to_bytes()) {
+            eprintln!("Could not write {}: {}", path, e);
+            status = 1;
+        }
+    }
+    if let Err(e) = chip8.finish_trace() {
+        eprintln!("Could not write the trace: {}", e);
+        status = 1;
+    }
+
+    match options.display.as_str() {
+        "ascii" => write_output(options.display_out.as_deref(), &display_ascii(&chip8)),
+        "pbm" => write_output(options.display_out.as_deref(), &display_pbm(&chip8)),
+        _ => {}
+    }
+    if let Some(path) = options.state.as_deref() {
+        write_output(Some(path), &state_json(&chip8));
+    }
+
+    process::exit(status);
+}
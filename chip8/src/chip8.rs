@@ -1,11 +1,20 @@
 use crate::constants::*;
+use crate::error::{Chip8Error, ErrorPolicy};
+use crate::mode::Mode;
+use crate::quirks::Quirks;
+use crate::rng::{RandomSource, SeededRng};
+use crate::trace::{TraceEntry, Tracer};
+
+use rand::Rng;
 
 pub struct Chip8 {
-    pub ram: [u8; RAM_SIZE],
+    pub ram: Vec<u8>,
     pub vregs: [u8; VREG_SIZE],
     pub stack: [u16; STACK_SIZE],
     pub keyboard: [bool; KEYBOARD_MAP_SIZE],
-    pub display: [bool; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+    // one byte per pixel, bit N set when the pixel is lit on plane N + 1
+    pub display: Vec<u8>,
+    pub rpl: [u8; RPL_SIZE],
 
     pub ireg: u16,
     pub program_counter: u16,
@@ -16,19 +25,46 @@ pub struct Chip8 {
 
     pub clear_flag: bool,
     pub display_flag: bool,
+    // SUPER-CHIP 128x64 mode, toggled by 00FF / 00FE
+    pub hires: bool,
+    // set by the SUPER-CHIP 00FD exit instruction
+    pub exited: bool,
+
+    pub quirks: Quirks,
+    pub mode: Mode,
+    pub error_policy: ErrorPolicy,
+    // address of the instruction currently executing, reported in errors
+    pub instruction_pc: u16,
+    // XO-CHIP bitplanes selected by FN01, drawn to and cleared together
+    pub planes: u8,
+    // XO-CHIP audio pattern played while the sound timer runs, and its pitch
+    pub audio_pattern: [u8; AUDIO_PATTERN_SIZE],
+    pub pitch: u8,
+    // set by DXYN under the display_wait quirk, cleared on the next timer tick
+    pub vblank_wait: bool,
+    // the key FX0A saw go down, which it takes once the key comes back up
+    pub key_wait: Option<u8>,
+    // feeds CXNN, seeded from the thread RNG unless given a seed
+    pub rng: Box<dyn RandomSource>,
+    // instructions executed since power on
+    pub cycles: u64,
+    pub tracer: Option<Box<dyn Tracer>>,
+    // where the small font lives for FX29, the big one follows it for FX30
+    pub font_base: u16,
 }
 
 impl Chip8 {
-    pub fn new() -> Self {
-        let chip8 = Chip8 {
-            ram: [0; RAM_SIZE],
+    pub fn new(quirks: Quirks) -> Self {
+        Chip8 {
+            ram: vec![0; RAM_SIZE],
             vregs: [0; VREG_SIZE],
             stack: [0; STACK_SIZE],
             keyboard: [false; KEYBOARD_MAP_SIZE],
-            display: [false; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            display: vec![0; DISPLAY_WIDTH * DISPLAY_HEIGHT],
+            rpl: [0; RPL_SIZE],
 
             ireg: 0,
-            program_counter: 0,
+            program_counter: PROGRAM_START as u16,
             stack_pointer: 0,
 
             delay_timer: 0,
@@ -36,58 +72,241 @@ impl Chip8 {
 
             clear_flag: false,
             display_flag: false,
-        };
+            hires: false,
+            exited: false,
+
+            quirks,
+            mode: Mode::Classic,
+            error_policy: ErrorPolicy::default(),
+            instruction_pc: PROGRAM_START as u16,
+            planes: 1,
+            audio_pattern: [0; AUDIO_PATTERN_SIZE],
+            pitch: DEFAULT_PITCH,
+            vblank_wait: false,
+            key_wait: None,
+            rng: Box::new(SeededRng::new(rand::thread_rng().gen())),
+            cycles: 0,
+            tracer: None,
+            font_base: DEFAULT_FONT_ADDR,
+        }
+    }
+
+    /// Switches the machine to `mode`, resizing RAM to match. Call before
+    /// loading fonts and the ROM, as RAM is cleared.
+    pub fn with_mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self.ram = vec![0; mode.ram_size()];
+        self
+    }
+
+    pub fn with_error_policy(mut self, error_policy: ErrorPolicy) -> Self {
+        self.error_policy = error_policy;
+        self
+    }
+
+    /// Makes CXNN reproducible: machines with the same seed draw the same
+    /// random bytes.
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.with_rng(SeededRng::new(seed))
+    }
+
+    pub fn with_rng(mut self, rng: impl RandomSource + 'static) -> Self {
+        self.rng = Box::new(rng);
+        self
+    }
 
-        chip8
+    /// Moves the fonts to `addr`. Call before `load_fonts`. Both fonts have
+    /// to fit below the program at 0x200, panics if they don't.
+    pub fn with_font_base(self, addr: u16) -> Self {
+        self.try_with_font_base(addr)
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
-    pub fn load_rom(&mut self, data: &[u8]) {
-        let start = 0x200 as usize;
-        let end = (0x200 as usize) + data.len();
+    /// `with_font_base` for addresses from files and the command line.
+    pub fn try_with_font_base(mut self, addr: u16) -> Result<Self, Chip8Error> {
+        if addr > MAX_FONT_BASE {
+            return Err(Chip8Error::InvalidFontBase { addr });
+        }
+        self.font_base = addr;
+        Ok(self)
+    }
+
+    /// Reports every executed instruction to `tracer`.
+    pub fn with_tracer(mut self, tracer: impl Tracer + 'static) -> Self {
+        self.tracer = Some(Box::new(tracer));
+        self
+    }
+
+    /// Removes the tracer, flushing it and reporting any write error.
+    pub fn finish_trace(&mut self) -> std::io::Result<()> {
+        match self.tracer.take() {
+            Some(mut tracer) => tracer.finish(),
+            None => Ok(()),
+        }
+    }
+
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let start = PROGRAM_START;
+        let max = self.ram.len() - start;
+        if data.len() > max {
+            return Err(Chip8Error::RomTooLarge {
+                size: data.len(),
+                max,
+            });
+        }
+
+        let end = start + data.len();
         self.ram[start..end].copy_from_slice(data);
+        Ok(())
     }
 
     pub fn load_fonts(&mut self) {
-        for i in 0..FONTSET_SIZE {
-            self.ram[i] = FONT_SET[i];
+        let small = self.font_base as usize;
+        self.ram[small..small + FONTSET_SIZE].copy_from_slice(&FONT_SET);
+
+        let big = self.big_font_addr() as usize;
+        self.ram[big..big + BIG_FONTSET_SIZE].copy_from_slice(&BIG_FONT_SET);
+    }
+
+    /// Replaces the built-in font with `data`: 80 bytes of 4x5 glyphs for
+    /// 0-F, optionally followed by 160 bytes of 8x10 SUPER-CHIP glyphs. The
+    /// built-in big font stays if there are none.
+    pub fn load_custom_font(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        if data.len() != FONTSET_SIZE && data.len() != FONTSET_SIZE + BIG_FONTSET_SIZE {
+            return Err(Chip8Error::InvalidFont { size: data.len() });
         }
+        let base = self.font_base as usize;
+        self.ram[base..base + data.len()].copy_from_slice(data);
+        Ok(())
     }
 
-    pub fn keypress(&mut self, idx: usize, pressed: bool) {
-        self.keyboard[idx] = pressed;
+    pub fn big_font_addr(&self) -> u16 {
+        self.font_base + FONTSET_SIZE as u16
     }
 
-    pub fn get_display(&self) -> &[bool] {
-        &self.display
+    pub fn keypress(&mut self, idx: usize, pressed: bool) -> Result<(), Chip8Error> {
+        let key = self
+            .keyboard
+            .get_mut(idx)
+            .ok_or(Chip8Error::InvalidKey { key: idx })?;
+        *key = pressed;
+        Ok(())
     }
 
-    pub fn tick(&mut self) {
-        // print!("{}[2J", 27 as char);
-        // for i in 0..self.display.len() {
-        //     print!("{} ", (if self.display[i] == true { 1 } else { 0 }));
-        // }
-        let opcode = self.fetch_opcode();
-        self.execute(opcode);
+    /// Returns the framebuffer along with its current width and height. Each
+    /// pixel is a bitmask of the planes it is lit on, so classic ROMs only
+    /// ever produce 0 or 1.
+    pub fn get_display(&self) -> (&[u8], usize, usize) {
+        (&self.display, self.display_width(), self.display_height())
+    }
+
+    pub fn display_width(&self) -> usize {
+        if self.hires {
+            HIRES_DISPLAY_WIDTH
+        } else {
+            DISPLAY_WIDTH
+        }
+    }
+
+    pub fn display_height(&self) -> usize {
+        if self.hires {
+            HIRES_DISPLAY_HEIGHT
+        } else {
+            DISPLAY_HEIGHT
+        }
+    }
+
+    /// Switches between the 64x32 and 128x64 resolutions, clearing the screen.
+    pub fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.display = vec![0; self.display_width() * self.display_height()];
+        self.clear_flag = true;
+    }
+
+    pub fn tick(&mut self) -> Result<(), Chip8Error> {
+        if self.vblank_wait || self.exited {
+            return Ok(());
+        }
+
+        self.instruction_pc = self.program_counter;
+        let cycle = self.cycles;
+        self.cycles += 1;
+        let result = self.fetch_opcode().and_then(|opcode| {
+            self.execute(opcode)?;
+            // the address word a long load read from after it
+            let long = (opcode == 0xF000)
+                .then(|| self.peek_word(self.instruction_pc.wrapping_add(2)).ok())
+                .flatten();
+            if let Some(tracer) = self.tracer.as_mut() {
+                tracer.trace(&TraceEntry {
+                    cycle,
+                    pc: self.instruction_pc,
+                    opcode,
+                    vregs: self.vregs,
+                    ireg: self.ireg,
+                    long,
+                });
+            }
+            Ok(())
+        });
+
+        match (result, self.error_policy) {
+            (Err(e), ErrorPolicy::Halt) => {
+                self.program_counter = self.instruction_pc;
+                Err(e)
+            }
+            _ => Ok(()),
+        }
     }
 
     pub fn tick_timers(&mut self) {
+        self.vblank_wait = false;
+
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
 
         if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                // BEEP
-            }
             self.sound_timer -= 1;
         }
     }
 
-    pub fn fetch_opcode(&mut self) -> u16 {
-        let higher_byte = self.ram[self.program_counter as usize] as u16;
-        let lower_byte = self.ram[(self.program_counter + 1) as usize] as u16;
-        let opcode = (higher_byte << 8) | lower_byte;
-        self.program_counter += 2;
-        opcode
+    /// Whether the buzzer should be sounding right now. Frontends poll this
+    /// once per frame after `tick_timers`.
+    pub fn is_sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    pub fn fetch_opcode(&mut self) -> Result<u16, Chip8Error> {
+        let opcode = self.peek_word(self.program_counter)?;
+        self.program_counter = self.program_counter.wrapping_add(2);
+        Ok(opcode)
+    }
+
+    /// Reads the big endian word at `addr` without moving the program counter.
+    pub fn peek_word(&self, addr: u16) -> Result<u16, Chip8Error> {
+        let higher_byte = self.read_ram(addr as usize)? as u16;
+        let lower_byte = self.read_ram(addr as usize + 1)? as u16;
+        Ok((higher_byte << 8) | lower_byte)
+    }
+
+    pub fn read_ram(&self, addr: usize) -> Result<u8, Chip8Error> {
+        self.ram
+            .get(addr)
+            .copied()
+            .ok_or(Chip8Error::MemoryOutOfBounds {
+                addr,
+                pc: self.instruction_pc,
+            })
+    }
+
+    pub fn write_ram(&mut self, addr: usize, value: u8) -> Result<(), Chip8Error> {
+        let pc = self.instruction_pc;
+        let byte = self
+            .ram
+            .get_mut(addr)
+            .ok_or(Chip8Error::MemoryOutOfBounds { addr, pc })?;
+        *byte = value;
+        Ok(())
     }
 }
@@ -30,17 +30,39 @@ RAM FORMATTING (for reference):
    0xFFF  --------------------------------  <--  End of RAM
 */
 pub const RAM_SIZE: usize = 4096;
+pub const PROGRAM_START: usize = 0x200;
+
+// XO-CHIP addresses the full 16 bit range
+pub const XO_RAM_SIZE: usize = 65536;
 
 pub const VREG_SIZE: usize = 16;
 pub const STACK_SIZE: usize = 16;
 
-pub const FONTSET_SIZE: usize = 16;
+pub const FONTSET_SIZE: usize = 80;
+pub const BIG_FONTSET_SIZE: usize = 160;
+// the highest address both fonts fit at below the program
+pub const MAX_FONT_BASE: u16 = (PROGRAM_START - FONTSET_SIZE - BIG_FONTSET_SIZE) as u16;
 pub const KEYBOARD_MAP_SIZE: usize = 16;
 
 pub const DISPLAY_WIDTH: usize = 64;
 pub const DISPLAY_HEIGHT: usize = 32;
 
-pub const FONT_SET: [u8; 80] = [
+// SUPER-CHIP high resolution mode
+pub const HIRES_DISPLAY_WIDTH: usize = 128;
+pub const HIRES_DISPLAY_HEIGHT: usize = 64;
+
+// RPL user flags, saved by FX75 and restored by FX85. SUPER-CHIP only has 8
+// of them but XO-CHIP allows all 16 registers
+pub const RPL_SIZE: usize = 16;
+
+// XO-CHIP audio pattern buffer, loaded by F002
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+pub const DEFAULT_PITCH: u8 = 64;
+
+// where most interpreters keep the font, the big font sits straight after it
+pub const DEFAULT_FONT_ADDR: u16 = 0x050;
+
+pub const FONT_SET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -58,3 +80,22 @@ pub const FONT_SET: [u8; 80] = [
     0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
+
+pub const BIG_FONT_SET: [u8; BIG_FONTSET_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
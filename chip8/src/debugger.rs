@@ -0,0 +1,382 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::asm::parse_number;
+use crate::chip8::Chip8;
+use crate::decode::Instruction;
+use crate::error::Chip8Error;
+
+/// Something a condition can look at. Registers read as their current
+/// value, `[addr]` as the byte in RAM there.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Vreg(u8),
+    I,
+    Pc,
+    Sp,
+    Delay,
+    Sound,
+    Ram(u16),
+    Value(u16),
+}
+
+impl Operand {
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        let upper = text.to_ascii_uppercase();
+        let operand = match upper.as_str() {
+            "I" => Operand::I,
+            "PC" => Operand::Pc,
+            "SP" => Operand::Sp,
+            "DT" => Operand::Delay,
+            "ST" => Operand::Sound,
+            _ => {
+                if let Some(addr) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+                    Operand::Ram(parse_address(addr)?)
+                } else if let Some(x) = parse_vreg(text) {
+                    Operand::Vreg(x)
+                } else {
+                    Operand::Value(parse_address(text)?)
+                }
+            }
+        };
+        Some(operand)
+    }
+
+    pub fn value(&self, chip8: &Chip8) -> u16 {
+        match *self {
+            Operand::Vreg(x) => chip8.vregs[x as usize] as u16,
+            Operand::I => chip8.ireg,
+            Operand::Pc => chip8.program_counter,
+            Operand::Sp => chip8.stack_pointer,
+            Operand::Delay => chip8.delay_timer as u16,
+            Operand::Sound => chip8.sound_timer as u16,
+            Operand::Ram(addr) => chip8.read_ram(addr as usize).unwrap_or(0) as u16,
+            Operand::Value(value) => value,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Vreg(x) => write!(f, "V{:X}", x),
+            Operand::I => write!(f, "I"),
+            Operand::Pc => write!(f, "PC"),
+            Operand::Sp => write!(f, "SP"),
+            Operand::Delay => write!(f, "DT"),
+            Operand::Sound => write!(f, "ST"),
+            Operand::Ram(addr) => write!(f, "[{:#05X}]", addr),
+            Operand::Value(value) => write!(f, "{:#04X}", value),
+        }
+    }
+}
+
+// longest first, so "<=" isn't read as "<"
+const COMPARISONS: [&str; 6] = ["==", "!=", "<=", ">=", "<", ">"];
+
+/// A comparison such as `V3 == 0x10` or `[0x300] > V0`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    pub left: Operand,
+    pub comparison: &'static str,
+    pub right: Operand,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let (pos, comparison) = COMPARISONS
+            .iter()
+            .filter_map(|&op| text.find(op).map(|pos| (pos, op)))
+            .min_by_key(|&(pos, op)| (pos, usize::MAX - op.len()))
+            .ok_or_else(|| format!("no comparison in '{}'", text))?;
+
+        let operand = |side: &str| {
+            Operand::parse(side).ok_or_else(|| format!("invalid operand '{}'", side.trim()))
+        };
+        Ok(Condition {
+            left: operand(&text[..pos])?,
+            comparison,
+            right: operand(&text[pos + comparison.len()..])?,
+        })
+    }
+
+    pub fn holds(&self, chip8: &Chip8) -> bool {
+        let (left, right) = (self.left.value(chip8), self.right.value(chip8));
+        match self.comparison {
+            "==" => left == right,
+            "!=" => left != right,
+            "<=" => left <= right,
+            ">=" => left >= right,
+            "<" => left < right,
+            _ => left > right,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.left, self.comparison, self.right)
+    }
+}
+
+/// A location whose value stops execution when it changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Watch {
+    Vreg(u8),
+    Ram(u16),
+}
+
+impl Watch {
+    /// `V3` for a register, anything else as a RAM address.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        match parse_vreg(text) {
+            Some(x) => Some(Watch::Vreg(x)),
+            None => parse_address(text).map(Watch::Ram),
+        }
+    }
+
+    fn value(&self, chip8: &Chip8) -> u8 {
+        match *self {
+            Watch::Vreg(x) => chip8.vregs[x as usize],
+            Watch::Ram(addr) => chip8.read_ram(addr as usize).unwrap_or(0),
+        }
+    }
+}
+
+impl fmt::Display for Watch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Watch::Vreg(x) => write!(f, "V{:X}", x),
+            Watch::Ram(addr) => write!(f, "[{:#05X}]", addr),
+        }
+    }
+}
+
+/// Why the debugger paused.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// About to execute the instruction at `addr`.
+    Breakpoint {
+        addr: u16,
+    },
+    Watchpoint {
+        watch: Watch,
+        old: u8,
+        new: u8,
+    },
+    /// A step, step over or step out finished.
+    Step,
+    Error(Chip8Error),
+    Exited,
+}
+
+impl fmt::Display for StopReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StopReason::Breakpoint { addr } => write!(f, "breakpoint at {:#05X}", addr),
+            StopReason::Watchpoint { watch, old, new } => {
+                write!(f, "{} changed from {:#04X} to {:#04X}", watch, old, new)
+            }
+            StopReason::Step => write!(f, "step"),
+            StopReason::Error(e) => write!(f, "{}", e),
+            StopReason::Exited => write!(f, "program exited"),
+        }
+    }
+}
+
+// what an armed step is waiting for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum StepTarget {
+    Instruction,
+    // back at this address with the stack this deep, i.e. the call returned
+    Return { addr: u16, depth: u16 },
+    // the stack shallower than this
+    Depth(u16),
+}
+
+/// Wraps `Chip8::tick` with breakpoints, watchpoints and stepping. Drive it
+/// with `tick` (or `run_frame`) instead of ticking the machine directly;
+/// while paused, ticks do nothing until `resume` or one of the steps.
+#[derive(Default)]
+pub struct Debugger {
+    breakpoints: BTreeMap<u16, Option<Condition>>,
+    watches: Vec<Watch>,
+    paused: bool,
+    step: Option<StepTarget>,
+    // lets execution leave the breakpoint it last stopped on
+    resuming: bool,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stops before the instruction at `addr` runs, or only when
+    /// `condition` holds at that point.
+    pub fn add_breakpoint(&mut self, addr: u16, condition: Option<Condition>) {
+        self.breakpoints.insert(addr, condition);
+    }
+
+    pub fn remove_breakpoint(&mut self, addr: u16) -> bool {
+        self.breakpoints.remove(&addr).is_some()
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = (u16, Option<&Condition>)> {
+        self.breakpoints
+            .iter()
+            .map(|(&addr, condition)| (addr, condition.as_ref()))
+    }
+
+    pub fn add_watch(&mut self, watch: Watch) {
+        if !self.watches.contains(&watch) {
+            self.watches.push(watch);
+        }
+    }
+
+    pub fn remove_watch(&mut self, watch: Watch) -> bool {
+        let before = self.watches.len();
+        self.watches.retain(|&w| w != watch);
+        self.watches.len() != before
+    }
+
+    pub fn watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.step = None;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+        self.resuming = true;
+    }
+
+    /// Runs exactly one instruction, then pauses.
+    pub fn step(&mut self) {
+        self.resume();
+        self.step = Some(StepTarget::Instruction);
+    }
+
+    /// Like `step`, but runs a 2NNN call through to its return.
+    pub fn step_over(&mut self, chip8: &Chip8) {
+        let at_call = chip8
+            .peek_word(chip8.program_counter)
+            .ok()
+            .and_then(Instruction::decode)
+            .is_some_and(|instruction| matches!(instruction, Instruction::Call(_)));
+
+        self.resume();
+        self.step = Some(if at_call {
+            StepTarget::Return {
+                addr: chip8.program_counter.wrapping_add(2),
+                depth: chip8.stack_pointer,
+            }
+        } else {
+            StepTarget::Instruction
+        });
+    }
+
+    /// Runs until the current subroutine returns to its caller.
+    pub fn step_out(&mut self, chip8: &Chip8) {
+        self.resume();
+        self.step = Some(StepTarget::Depth(chip8.stack_pointer));
+    }
+
+    /// Executes one instruction unless paused or a breakpoint on it stops
+    /// execution first. Returns why execution stopped, if it did.
+    pub fn tick(&mut self, chip8: &mut Chip8) -> Option<StopReason> {
+        if self.paused {
+            return None;
+        }
+
+        let pc = chip8.program_counter;
+        if !self.resuming {
+            if let Some(condition) = self.breakpoints.get(&pc) {
+                if condition.is_none_or(|condition| condition.holds(chip8)) {
+                    return self.stop(StopReason::Breakpoint { addr: pc });
+                }
+            }
+        }
+
+        let before: Vec<u8> = self.watches.iter().map(|w| w.value(chip8)).collect();
+        let waiting = chip8.vblank_wait;
+        // nothing runs while waiting for vblank, so the breakpoint being
+        // resumed from is still ahead
+        if !waiting {
+            self.resuming = false;
+        }
+        if let Err(e) = chip8.tick() {
+            return self.stop(StopReason::Error(e));
+        }
+        if chip8.exited {
+            return self.stop(StopReason::Exited);
+        }
+
+        for (&watch, old) in self.watches.iter().zip(before) {
+            let new = watch.value(chip8);
+            if new != old {
+                return self.stop(StopReason::Watchpoint { watch, old, new });
+            }
+        }
+
+        // a tick spent waiting for vblank doesn't count as a step
+        let done = match self.step {
+            _ if waiting => false,
+            Some(StepTarget::Instruction) => true,
+            Some(StepTarget::Return { addr, depth }) => {
+                chip8.program_counter == addr && chip8.stack_pointer == depth
+            }
+            Some(StepTarget::Depth(depth)) => chip8.stack_pointer < depth,
+            None => false,
+        };
+        if done {
+            return self.stop(StopReason::Step);
+        }
+        None
+    }
+
+    /// Runs up to `cycles` instructions and then the timers, the way
+    /// `Scheduler::run_frame` does. Timers don't run if execution stopped
+    /// part way through.
+    pub fn run_frame(&mut self, chip8: &mut Chip8, cycles: u64) -> Option<StopReason> {
+        if self.paused {
+            return None;
+        }
+        for _ in 0..cycles {
+            if let Some(reason) = self.tick(chip8) {
+                return Some(reason);
+            }
+        }
+        chip8.tick_timers();
+        None
+    }
+
+    fn stop(&mut self, reason: StopReason) -> Option<StopReason> {
+        self.paused = true;
+        self.step = None;
+        Some(reason)
+    }
+}
+
+fn parse_vreg(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['V', 'v'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+/// A number as the assembler reads them: decimal, 0x / # hex or 0b / %
+/// binary.
+pub fn parse_address(text: &str) -> Option<u16> {
+    parse_number(text.trim()).and_then(|n| u16::try_from(n).ok())
+}
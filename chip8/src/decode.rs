@@ -0,0 +1,319 @@
+use std::fmt;
+
+/// A decoded opcode. Register operands are indices into `vregs`, so `x: 3`
+/// means V3. `Display` prints the instruction in Cowgod's mnemonics.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Instruction {
+    // 00E0
+    Clear,
+    // 00EE
+    Return,
+    // 00CN (SUPER-CHIP)
+    ScrollDown(u8),
+    // 00DN (XO-CHIP)
+    ScrollUp(u8),
+    // 00FB (SUPER-CHIP)
+    ScrollRight,
+    // 00FC (SUPER-CHIP)
+    ScrollLeft,
+    // 00FD (SUPER-CHIP)
+    Exit,
+    // 00FE (SUPER-CHIP)
+    Lores,
+    // 00FF (SUPER-CHIP)
+    Hires,
+    // 1NNN
+    Jump(u16),
+    // 2NNN
+    Call(u16),
+    // 3XNN
+    SkipEqImm { x: u8, nn: u8 },
+    // 4XNN
+    SkipNeImm { x: u8, nn: u8 },
+    // 5XY0
+    SkipEqReg { x: u8, y: u8 },
+    // 5XY2 (XO-CHIP)
+    SaveRange { x: u8, y: u8 },
+    // 5XY3 (XO-CHIP)
+    LoadRange { x: u8, y: u8 },
+    // 6XNN
+    LoadImm { x: u8, nn: u8 },
+    // 7XNN
+    AddImm { x: u8, nn: u8 },
+    // 8XY0
+    Move { x: u8, y: u8 },
+    // 8XY1
+    Or { x: u8, y: u8 },
+    // 8XY2
+    And { x: u8, y: u8 },
+    // 8XY3
+    Xor { x: u8, y: u8 },
+    // 8XY4
+    Add { x: u8, y: u8 },
+    // 8XY5
+    Sub { x: u8, y: u8 },
+    // 8XY6
+    ShiftRight { x: u8, y: u8 },
+    // 8XY7
+    SubN { x: u8, y: u8 },
+    // 8XYE
+    ShiftLeft { x: u8, y: u8 },
+    // 9XY0
+    SkipNeReg { x: u8, y: u8 },
+    // ANNN
+    LoadI(u16),
+    // BNNN, BXNN under the jump_uses_vx quirk
+    JumpOffset { x: u8, nnn: u16 },
+    // CXNN
+    Random { x: u8, nn: u8 },
+    // DXYN, and DXY0 for SUPER-CHIP 16x16 sprites
+    Draw { x: u8, y: u8, n: u8 },
+    // EX9E
+    SkipKey { x: u8 },
+    // EXA1
+    SkipNotKey { x: u8 },
+    // F000 NNNN (XO-CHIP), the address is the word after the opcode
+    LoadLongI,
+    // FN01 (XO-CHIP)
+    Planes(u8),
+    // F002 (XO-CHIP)
+    LoadAudio,
+    // FX07
+    GetDelay { x: u8 },
+    // FX0A
+    WaitKey { x: u8 },
+    // FX15
+    SetDelay { x: u8 },
+    // FX18
+    SetSound { x: u8 },
+    // FX1E
+    AddI { x: u8 },
+    // FX29
+    Font { x: u8 },
+    // FX30 (SUPER-CHIP)
+    BigFont { x: u8 },
+    // FX33
+    Bcd { x: u8 },
+    // FX3A (XO-CHIP)
+    Pitch { x: u8 },
+    // FX55
+    Store { x: u8 },
+    // FX65
+    Load { x: u8 },
+    // FX75 (SUPER-CHIP)
+    SaveFlags { x: u8 },
+    // FX85 (SUPER-CHIP)
+    LoadFlags { x: u8 },
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Option<Instruction> {
+        let first_digit = (opcode & 0xF000) >> 12;
+        let second_digit = (opcode & 0x0F00) >> 8;
+        let third_digit = (opcode & 0x00F0) >> 4;
+        let fourth_digit = opcode & 0x000F;
+
+        let x = second_digit as u8;
+        let y = third_digit as u8;
+        let n = fourth_digit as u8;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+
+        let instruction = match (first_digit, second_digit, third_digit, fourth_digit) {
+            (0, 0, 0xE, 0) => Instruction::Clear,
+            (0, 0, 0xE, 0xE) => Instruction::Return,
+            (0, 0, 0xC, _) => Instruction::ScrollDown(n),
+            (0, 0, 0xD, _) => Instruction::ScrollUp(n),
+            (0, 0, 0xF, 0xB) => Instruction::ScrollRight,
+            (0, 0, 0xF, 0xC) => Instruction::ScrollLeft,
+            (0, 0, 0xF, 0xD) => Instruction::Exit,
+            (0, 0, 0xF, 0xE) => Instruction::Lores,
+            (0, 0, 0xF, 0xF) => Instruction::Hires,
+            (1, _, _, _) => Instruction::Jump(nnn),
+            (2, _, _, _) => Instruction::Call(nnn),
+            (3, _, _, _) => Instruction::SkipEqImm { x, nn },
+            (4, _, _, _) => Instruction::SkipNeImm { x, nn },
+            (5, _, _, 0) => Instruction::SkipEqReg { x, y },
+            (5, _, _, 2) => Instruction::SaveRange { x, y },
+            (5, _, _, 3) => Instruction::LoadRange { x, y },
+            (6, _, _, _) => Instruction::LoadImm { x, nn },
+            (7, _, _, _) => Instruction::AddImm { x, nn },
+            (8, _, _, 0) => Instruction::Move { x, y },
+            (8, _, _, 1) => Instruction::Or { x, y },
+            (8, _, _, 2) => Instruction::And { x, y },
+            (8, _, _, 3) => Instruction::Xor { x, y },
+            (8, _, _, 4) => Instruction::Add { x, y },
+            (8, _, _, 5) => Instruction::Sub { x, y },
+            (8, _, _, 6) => Instruction::ShiftRight { x, y },
+            (8, _, _, 7) => Instruction::SubN { x, y },
+            (8, _, _, 0xE) => Instruction::ShiftLeft { x, y },
+            (9, _, _, 0) => Instruction::SkipNeReg { x, y },
+            (0xA, _, _, _) => Instruction::LoadI(nnn),
+            (0xB, _, _, _) => Instruction::JumpOffset { x, nnn },
+            (0xC, _, _, _) => Instruction::Random { x, nn },
+            (0xD, _, _, _) => Instruction::Draw { x, y, n },
+            (0xE, _, 9, 0xE) => Instruction::SkipKey { x },
+            (0xE, _, 0xA, 1) => Instruction::SkipNotKey { x },
+            (0xF, 0, 0, 0) => Instruction::LoadLongI,
+            (0xF, _, 0, 1) => Instruction::Planes(x),
+            (0xF, 0, 0, 2) => Instruction::LoadAudio,
+            (0xF, _, 0, 7) => Instruction::GetDelay { x },
+            (0xF, _, 0, 0xA) => Instruction::WaitKey { x },
+            (0xF, _, 1, 5) => Instruction::SetDelay { x },
+            (0xF, _, 1, 8) => Instruction::SetSound { x },
+            (0xF, _, 1, 0xE) => Instruction::AddI { x },
+            (0xF, _, 2, 9) => Instruction::Font { x },
+            (0xF, _, 3, 0) => Instruction::BigFont { x },
+            (0xF, _, 3, 3) => Instruction::Bcd { x },
+            (0xF, _, 3, 0xA) => Instruction::Pitch { x },
+            (0xF, _, 5, 5) => Instruction::Store { x },
+            (0xF, _, 6, 5) => Instruction::Load { x },
+            (0xF, _, 7, 5) => Instruction::SaveFlags { x },
+            (0xF, _, 8, 5) => Instruction::LoadFlags { x },
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    /// The inverse of `decode`. Operands are masked to the bits the opcode
+    /// has room for.
+    pub fn encode(&self) -> u16 {
+        let xy = |op: u16, x: u8, y: u8, n: u16| {
+            op | ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4) | n
+        };
+        let xnn = |op: u16, x: u8, nn: u8| op | ((x as u16 & 0xF) << 8) | nn as u16;
+
+        match *self {
+            Instruction::Clear => 0x00E0,
+            Instruction::Return => 0x00EE,
+            Instruction::ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            Instruction::ScrollUp(n) => 0x00D0 | (n as u16 & 0xF),
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Lores => 0x00FE,
+            Instruction::Hires => 0x00FF,
+            Instruction::Jump(addr) => 0x1000 | (addr & 0xFFF),
+            Instruction::Call(addr) => 0x2000 | (addr & 0xFFF),
+            Instruction::SkipEqImm { x, nn } => xnn(0x3000, x, nn),
+            Instruction::SkipNeImm { x, nn } => xnn(0x4000, x, nn),
+            Instruction::SkipEqReg { x, y } => xy(0x5000, x, y, 0),
+            Instruction::SaveRange { x, y } => xy(0x5000, x, y, 2),
+            Instruction::LoadRange { x, y } => xy(0x5000, x, y, 3),
+            Instruction::LoadImm { x, nn } => xnn(0x6000, x, nn),
+            Instruction::AddImm { x, nn } => xnn(0x7000, x, nn),
+            Instruction::Move { x, y } => xy(0x8000, x, y, 0),
+            Instruction::Or { x, y } => xy(0x8000, x, y, 1),
+            Instruction::And { x, y } => xy(0x8000, x, y, 2),
+            Instruction::Xor { x, y } => xy(0x8000, x, y, 3),
+            Instruction::Add { x, y } => xy(0x8000, x, y, 4),
+            Instruction::Sub { x, y } => xy(0x8000, x, y, 5),
+            Instruction::ShiftRight { x, y } => xy(0x8000, x, y, 6),
+            Instruction::SubN { x, y } => xy(0x8000, x, y, 7),
+            Instruction::ShiftLeft { x, y } => xy(0x8000, x, y, 0xE),
+            Instruction::SkipNeReg { x, y } => xy(0x9000, x, y, 0),
+            Instruction::LoadI(addr) => 0xA000 | (addr & 0xFFF),
+            Instruction::JumpOffset { x, nnn } => 0xB000 | ((x as u16 & 0xF) << 8) | (nnn & 0xFFF),
+            Instruction::Random { x, nn } => xnn(0xC000, x, nn),
+            Instruction::Draw { x, y, n } => xy(0xD000, x, y, n as u16 & 0xF),
+            Instruction::SkipKey { x } => xnn(0xE000, x, 0x9E),
+            Instruction::SkipNotKey { x } => xnn(0xE000, x, 0xA1),
+            Instruction::LoadLongI => 0xF000,
+            Instruction::Planes(n) => xnn(0xF000, n, 0x01),
+            Instruction::LoadAudio => 0xF002,
+            Instruction::GetDelay { x } => xnn(0xF000, x, 0x07),
+            Instruction::WaitKey { x } => xnn(0xF000, x, 0x0A),
+            Instruction::SetDelay { x } => xnn(0xF000, x, 0x15),
+            Instruction::SetSound { x } => xnn(0xF000, x, 0x18),
+            Instruction::AddI { x } => xnn(0xF000, x, 0x1E),
+            Instruction::Font { x } => xnn(0xF000, x, 0x29),
+            Instruction::BigFont { x } => xnn(0xF000, x, 0x30),
+            Instruction::Bcd { x } => xnn(0xF000, x, 0x33),
+            Instruction::Pitch { x } => xnn(0xF000, x, 0x3A),
+            Instruction::Store { x } => xnn(0xF000, x, 0x55),
+            Instruction::Load { x } => xnn(0xF000, x, 0x65),
+            Instruction::SaveFlags { x } => xnn(0xF000, x, 0x75),
+            Instruction::LoadFlags { x } => xnn(0xF000, x, 0x85),
+        }
+    }
+
+    /// Whether the instruction only exists on XO-CHIP.
+    pub fn is_xochip(&self) -> bool {
+        matches!(
+            self,
+            Instruction::ScrollUp(_)
+                | Instruction::SaveRange { .. }
+                | Instruction::LoadRange { .. }
+                | Instruction::LoadLongI
+                | Instruction::Planes(_)
+                | Instruction::LoadAudio
+                | Instruction::Pitch { .. }
+        )
+    }
+
+    /// Size in bytes, including the trailing address word of F000 NNNN.
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::LoadLongI => 4,
+            _ => 2,
+        }
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollUp(n) => write!(f, "SCU {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Lores => write!(f, "LOW"),
+            Instruction::Hires => write!(f, "HIGH"),
+            Instruction::Jump(addr) => write!(f, "JP {:#05X}", addr),
+            Instruction::Call(addr) => write!(f, "CALL {:#05X}", addr),
+            Instruction::SkipEqImm { x, nn } => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            Instruction::SkipNeImm { x, nn } => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            Instruction::SkipEqReg { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange { x, y } => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            Instruction::LoadImm { x, nn } => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            Instruction::AddImm { x, nn } => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Instruction::Move { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubN { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeReg { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI(addr) => write!(f, "LD I, {:#05X}", addr),
+            Instruction::JumpOffset { nnn, .. } => write!(f, "JP V0, {:#05X}", nnn),
+            Instruction::Random { x, nn } => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKey { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipNotKey { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadLongI => write!(f, "LD I, LONG"),
+            Instruction::Planes(n) => write!(f, "PLANE {}", n),
+            Instruction::LoadAudio => write!(f, "AUDIO"),
+            Instruction::GetDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::Font { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::BigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::Pitch { x } => write!(f, "PITCH V{:X}", x),
+            Instruction::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::SaveFlags { x } => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::BTreeSet;
+
+use crate::decode::Instruction;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    /// Cowgod's Chip-8 technical reference mnemonics, e.g. `LD VA, 0x02`.
+    Cowgod,
+    /// Octo assembly, e.g. `va := 0x02`.
+    Octo,
+}
+
+impl Syntax {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "cowgod" => Some(Syntax::Cowgod),
+            "octo" => Some(Syntax::Octo),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    Code {
+        addr: u16,
+        opcode: u16,
+        instruction: Instruction,
+        // the address word following an F000 NNNN long load
+        long: Option<u16>,
+    },
+    Data {
+        addr: u16,
+        byte: u8,
+    },
+}
+
+impl Line {
+    pub fn addr(&self) -> u16 {
+        match self {
+            Line::Code { addr, .. } | Line::Data { addr, .. } => *addr,
+        }
+    }
+
+    pub fn format(&self, syntax: Syntax) -> String {
+        match self {
+            Line::Code {
+                addr,
+                opcode,
+                instruction,
+                long,
+            } => {
+                let raw = match long {
+                    Some(word) => format!("{:04X} {:04X}", opcode, word),
+                    None => format!("{:04X}", opcode),
+                };
+                let text = match (syntax, long) {
+                    (Syntax::Cowgod, Some(word)) => format!("LD I, LONG {:#06X}", word),
+                    (Syntax::Cowgod, None) => instruction.to_string(),
+                    (Syntax::Octo, _) => octo(instruction, long.unwrap_or(0)),
+                };
+                format!("{:#05X}  {:<9}  {}", addr, raw, text)
+            }
+            Line::Data { addr, byte } => {
+                let (text, comment) = match syntax {
+                    Syntax::Cowgod => (format!("DB {:#04X}", byte), ';'),
+                    Syntax::Octo => (format!("{:#04X}", byte), '#'),
+                };
+                let bits: String = (0..8)
+                    .map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' })
+                    .collect();
+                let raw = format!("{:02X}", byte);
+                format!(
+                    "{:#05X}  {:<9}  {:<14} {} {}",
+                    addr, raw, text, comment, bits
+                )
+            }
+        }
+    }
+}
+
+/// Formats an instruction as Octo assembly. `long` is only used by the
+/// XO-CHIP `i := long NNNN`.
+pub fn octo(instruction: &Instruction, long: u16) -> String {
+    match *instruction {
+        Instruction::Clear => "clear".to_string(),
+        Instruction::Return => "return".to_string(),
+        Instruction::ScrollDown(n) => format!("scroll-down {}", n),
+        Instruction::ScrollUp(n) => format!("scroll-up {}", n),
+        Instruction::ScrollRight => "scroll-right".to_string(),
+        Instruction::ScrollLeft => "scroll-left".to_string(),
+        Instruction::Exit => "exit".to_string(),
+        Instruction::Lores => "lores".to_string(),
+        Instruction::Hires => "hires".to_string(),
+        Instruction::Jump(addr) => format!("jump {:#05X}", addr),
+        Instruction::Call(addr) => format!(":call {:#05X}", addr),
+        // Octo's conditionals say when the next instruction runs, which is
+        // the opposite of when it gets skipped
+        Instruction::SkipEqImm { x, nn } => format!("if v{:x} != {:#04X} then", x, nn),
+        Instruction::SkipNeImm { x, nn } => format!("if v{:x} == {:#04X} then", x, nn),
+        Instruction::SkipEqReg { x, y } => format!("if v{:x} != v{:x} then", x, y),
+        Instruction::SkipNeReg { x, y } => format!("if v{:x} == v{:x} then", x, y),
+        Instruction::SaveRange { x, y } => format!("save v{:x} - v{:x}", x, y),
+        Instruction::LoadRange { x, y } => format!("load v{:x} - v{:x}", x, y),
+        Instruction::LoadImm { x, nn } => format!("v{:x} := {:#04X}", x, nn),
+        Instruction::AddImm { x, nn } => format!("v{:x} += {:#04X}", x, nn),
+        Instruction::Move { x, y } => format!("v{:x} := v{:x}", x, y),
+        Instruction::Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+        Instruction::And { x, y } => format!("v{:x} &= v{:x}", x, y),
+        Instruction::Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+        Instruction::Add { x, y } => format!("v{:x} += v{:x}", x, y),
+        Instruction::Sub { x, y } => format!("v{:x} -= v{:x}", x, y),
+        Instruction::ShiftRight { x, y } => format!("v{:x} >>= v{:x}", x, y),
+        Instruction::SubN { x, y } => format!("v{:x} =- v{:x}", x, y),
+        Instruction::ShiftLeft { x, y } => format!("v{:x} <<= v{:x}", x, y),
+        Instruction::LoadI(addr) => format!("i := {:#05X}", addr),
+        Instruction::JumpOffset { nnn, .. } => format!("jump0 {:#05X}", nnn),
+        Instruction::Random { x, nn } => format!("v{:x} := random {:#04X}", x, nn),
+        Instruction::Draw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+        Instruction::SkipKey { x } => format!("if v{:x} -key then", x),
+        Instruction::SkipNotKey { x } => format!("if v{:x} key then", x),
+        Instruction::LoadLongI => format!("i := long {:#06X}", long),
+        Instruction::Planes(n) => format!("plane {}", n),
+        Instruction::LoadAudio => "audio".to_string(),
+        Instruction::GetDelay { x } => format!("v{:x} := delay", x),
+        Instruction::WaitKey { x } => format!("v{:x} := key", x),
+        Instruction::SetDelay { x } => format!("delay := v{:x}", x),
+        Instruction::SetSound { x } => format!("buzzer := v{:x}", x),
+        Instruction::AddI { x } => format!("i += v{:x}", x),
+        Instruction::Font { x } => format!("i := hex v{:x}", x),
+        Instruction::BigFont { x } => format!("i := bighex v{:x}", x),
+        Instruction::Bcd { x } => format!("bcd v{:x}", x),
+        Instruction::Pitch { x } => format!("pitch := v{:x}", x),
+        Instruction::Store { x } => format!("save v{:x}", x),
+        Instruction::Load { x } => format!("load v{:x}", x),
+        Instruction::SaveFlags { x } => format!("saveflags v{:x}", x),
+        Instruction::LoadFlags { x } => format!("loadflags v{:x}", x),
+    }
+}
+
+/// Disassembles a ROM loaded at `origin`. Code is found by recursive descent
+/// from the entry point, following jumps, calls and both sides of every
+/// skip, so anything never reached that way (sprites, tables) comes out as
+/// data instead of being misread as instructions.
+pub fn disassemble(rom: &[u8], origin: u16) -> Vec<Line> {
+    let end = origin as usize + rom.len();
+    let word_at = |addr: usize| -> Option<u16> {
+        if addr < origin as usize || addr + 1 >= end {
+            return None;
+        }
+        let offset = addr - origin as usize;
+        Some(((rom[offset] as u16) << 8) | rom[offset + 1] as u16)
+    };
+
+    let mut code: BTreeSet<usize> = BTreeSet::new();
+    let mut pending = vec![origin as usize];
+
+    while let Some(addr) = pending.pop() {
+        if code.contains(&addr) {
+            continue;
+        }
+        let Some(instruction) = word_at(addr).and_then(Instruction::decode) else {
+            continue;
+        };
+        if instruction == Instruction::LoadLongI && word_at(addr + 2).is_none() {
+            continue;
+        }
+        code.insert(addr);
+
+        let next = addr + instruction.size() as usize;
+        match instruction {
+            Instruction::Jump(target) => pending.push(target as usize),
+            Instruction::Call(target) => {
+                pending.push(target as usize);
+                pending.push(next);
+            }
+            // a computed jump or the end of the program, nothing to follow
+            Instruction::Return | Instruction::Exit | Instruction::JumpOffset { .. } => {}
+            Instruction::SkipEqImm { .. }
+            | Instruction::SkipNeImm { .. }
+            | Instruction::SkipEqReg { .. }
+            | Instruction::SkipNeReg { .. }
+            | Instruction::SkipKey { .. }
+            | Instruction::SkipNotKey { .. } => {
+                pending.push(next);
+                let skipped = match word_at(next) {
+                    Some(0xF000) => 4,
+                    _ => 2,
+                };
+                pending.push(next + skipped);
+            }
+            _ => pending.push(next),
+        }
+    }
+
+    linear_from(rom, origin, &code)
+}
+
+/// Disassembles every word in the ROM as code, the way a naive hex dump
+/// reader would. Undecodable words come out as data.
+pub fn disassemble_linear(rom: &[u8], origin: u16) -> Vec<Line> {
+    let mut code = BTreeSet::new();
+    let mut addr = origin as usize;
+    while addr + 1 < origin as usize + rom.len() {
+        code.insert(addr);
+        addr += 2;
+    }
+    linear_from(rom, origin, &code)
+}
+
+fn linear_from(rom: &[u8], origin: u16, code: &BTreeSet<usize>) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let addr = origin as usize + offset;
+        let opcode = rom
+            .get(offset..offset + 2)
+            .map(|bytes| ((bytes[0] as u16) << 8) | bytes[1] as u16);
+
+        match (code.contains(&addr), opcode.and_then(Instruction::decode)) {
+            (true, Some(Instruction::LoadLongI)) if offset + 3 < rom.len() => {
+                let word = ((rom[offset + 2] as u16) << 8) | rom[offset + 3] as u16;
+                lines.push(Line::Code {
+                    addr: addr as u16,
+                    opcode: 0xF000,
+                    instruction: Instruction::LoadLongI,
+                    long: Some(word),
+                });
+                offset += 4;
+            }
+            (true, Some(instruction)) if instruction != Instruction::LoadLongI => {
+                lines.push(Line::Code {
+                    addr: addr as u16,
+                    opcode: opcode.unwrap(),
+                    instruction,
+                    long: None,
+                });
+                offset += 2;
+            }
+            _ => {
+                lines.push(Line::Data {
+                    addr: addr as u16,
+                    byte: rom[offset],
+                });
+                offset += 1;
+            }
+        }
+    }
+
+    lines
+}
@@ -0,0 +1,85 @@
+use crate::chip8::Chip8;
+use crate::mode::Mode;
+
+/// The display as text, one line per row: `#` for lit pixels and `.` for
+/// unlit ones. XO-CHIP pixels lit only on the second plane show as `+` and
+/// on both planes as `@`.
+pub fn display_ascii(chip8: &Chip8) -> String {
+    let (display, width, _) = chip8.get_display();
+    let mut out = String::with_capacity(display.len() + display.len() / width);
+
+    for row in display.chunks(width) {
+        for pixel in row {
+            out.push(match pixel & 0x3 {
+                0 => '.',
+                1 => '#',
+                2 => '+',
+                _ => '@',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// The display as a plain (P1) PBM image, any lit plane counting as black.
+pub fn display_pbm(chip8: &Chip8) -> String {
+    let (display, width, height) = chip8.get_display();
+    let mut out = format!("P1\n{} {}\n", width, height);
+
+    for row in display.chunks(width) {
+        let bits: Vec<&str> = row
+            .iter()
+            .map(|&pixel| if pixel != 0 { "1" } else { "0" })
+            .collect();
+        out.push_str(&bits.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+/// Registers, timers, stack and memory as a JSON object. Memory is one hex
+/// string to keep the output a manageable size.
+pub fn state_json(chip8: &Chip8) -> String {
+    let list = |values: Vec<String>| values.join(", ");
+    let memory: String = chip8
+        .ram
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let mode = match chip8.mode {
+        Mode::Classic => "classic",
+        Mode::XoChip => "xochip",
+    };
+
+    format!(
+        concat!(
+            "{{\n",
+            "  \"mode\": \"{}\",\n",
+            "  \"pc\": {},\n",
+            "  \"i\": {},\n",
+            "  \"sp\": {},\n",
+            "  \"v\": [{}],\n",
+            "  \"stack\": [{}],\n",
+            "  \"delay_timer\": {},\n",
+            "  \"sound_timer\": {},\n",
+            "  \"keyboard\": [{}],\n",
+            "  \"hires\": {},\n",
+            "  \"exited\": {},\n",
+            "  \"memory\": \"{}\"\n",
+            "}}\n"
+        ),
+        mode,
+        chip8.program_counter,
+        chip8.ireg,
+        chip8.stack_pointer,
+        list(chip8.vregs.iter().map(u8::to_string).collect()),
+        list(chip8.stack.iter().map(u16::to_string).collect()),
+        chip8.delay_timer,
+        chip8.sound_timer,
+        list(chip8.keyboard.iter().map(bool::to_string).collect()),
+        chip8.hires,
+        chip8.exited,
+        memory,
+    )
+}
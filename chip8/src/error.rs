@@ -0,0 +1,67 @@
+use std::fmt;
+
+/// Everything that can go wrong while loading or running a ROM. `pc` is
+/// always the address of the instruction that faulted.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Chip8Error {
+    RomTooLarge { size: usize, max: usize },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    MemoryOutOfBounds { addr: usize, pc: u16 },
+    InvalidKey { key: usize },
+    UnknownOpcode { opcode: u16, pc: u16 },
+    InvalidSaveState { reason: String },
+    InvalidFont { size: usize },
+    InvalidFontBase { addr: u16 },
+    InvalidMovie { reason: String },
+    MovieDesync { frame: u32 },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {} bytes, at most {} fit in memory", size, max)
+            }
+            Chip8Error::StackOverflow { pc } => write!(f, "stack overflow at {:#05x}", pc),
+            Chip8Error::StackUnderflow { pc } => write!(f, "stack underflow at {:#05x}", pc),
+            Chip8Error::MemoryOutOfBounds { addr, pc } => {
+                write!(
+                    f,
+                    "memory access to {:#06x} out of bounds at {:#05x}",
+                    addr, pc
+                )
+            }
+            Chip8Error::InvalidKey { key } => write!(f, "invalid key {:#x}", key),
+            Chip8Error::UnknownOpcode { opcode, pc } => {
+                write!(f, "unknown opcode {:#06x} at {:#05x}", opcode, pc)
+            }
+            Chip8Error::InvalidSaveState { reason } => write!(f, "invalid save state: {}", reason),
+            Chip8Error::InvalidFont { size } => write!(
+                f,
+                "font is {} bytes, expected 80 or 240 with the big font",
+                size
+            ),
+            Chip8Error::InvalidFontBase { addr } => {
+                write!(f, "fonts at {:#05x} would overlap the program", addr)
+            }
+            Chip8Error::InvalidMovie { reason } => write!(f, "invalid movie: {}", reason),
+            Chip8Error::MovieDesync { frame } => {
+                write!(f, "movie desynced, state differs at frame {}", frame)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// What `Chip8::tick` does when an instruction faults.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Return the error and leave the program counter on the faulting
+    /// instruction, so every further tick reports it again.
+    #[default]
+    Halt,
+    /// Abandon the faulting instruction and carry on with the next one.
+    Ignore,
+}
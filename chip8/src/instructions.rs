@@ -1,304 +1,316 @@
-use std::usize;
-
-use rand::Rng;
-
 use crate::{
     chip8::Chip8,
-    constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH},
+    constants::{AUDIO_PATTERN_SIZE, RPL_SIZE, STACK_SIZE},
+    decode::Instruction,
+    error::Chip8Error,
+    mode::Mode,
 };
 
 impl Chip8 {
-    pub fn execute(&mut self, opcode: u16) {
-        let first_digit = (opcode & 0xF000) >> 12;
-        let second_digit = (opcode & 0x0F00) >> 8;
-        let third_digit = (opcode & 0x00F0) >> 4;
-        let fourth_digit = opcode & 0x000F;
+    pub fn execute(&mut self, opcode: u16) -> Result<(), Chip8Error> {
+        let unknown = Chip8Error::UnknownOpcode {
+            opcode,
+            pc: self.instruction_pc,
+        };
+
+        match Instruction::decode(opcode) {
+            Some(instruction) if self.mode == Mode::XoChip || !instruction.is_xochip() => {
+                self.execute_instruction(instruction)
+            }
+            // 0NNN - call a COSMAC VIP machine code routine, old ROMs still
+            // have them and interpreters have always skipped over them
+            None if opcode & 0xF000 == 0 && self.mode == Mode::Classic => Ok(()),
+            _ => Err(unknown),
+        }
+    }
 
-        match (first_digit, second_digit, third_digit, fourth_digit) {
+    pub fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        match instruction {
             // clear screen
-            (0x0, 0x0, 0xE, 0) => {
-                for i in 0..self.display.len() {
-                    self.display[i] = false;
+            Instruction::Clear => {
+                for pixel in self.display.iter_mut() {
+                    *pixel &= !self.planes;
                 }
                 self.clear_flag = true;
             }
             // 00EE - return from subroutine
-            (0, 0, 0xE, 0xE) => {
+            Instruction::Return => {
+                if self.stack_pointer == 0 {
+                    return Err(Chip8Error::StackUnderflow {
+                        pc: self.instruction_pc,
+                    });
+                }
                 self.stack_pointer -= 1;
                 let last_addr = self.stack[self.stack_pointer as usize];
 
                 self.program_counter = last_addr;
             }
-            (0, 0, _, _) => {
-                // unsupported 0x0 0x0 opcode
+            // 00CN - scroll down N lines
+            Instruction::ScrollDown(n) => {
+                self.scroll(0, n as isize);
+            }
+            // 00DN - XO-CHIP scroll up N lines
+            Instruction::ScrollUp(n) => {
+                self.scroll(0, -(n as isize));
+            }
+            // 00FB - scroll right 4 pixels
+            Instruction::ScrollRight => {
+                self.scroll(4, 0);
+            }
+            // 00FC - scroll left 4 pixels
+            Instruction::ScrollLeft => {
+                self.scroll(-4, 0);
+            }
+            // 00FD - exit the interpreter
+            Instruction::Exit => {
+                self.exited = true;
+            }
+            // 00FE - low resolution
+            Instruction::Lores => {
+                self.set_hires(false);
+            }
+            // 00FF - high resolution
+            Instruction::Hires => {
+                self.set_hires(true);
             }
             // set pc to addr
-            (1, _, _, _) => {
-                let address = opcode & 0xFFF;
+            Instruction::Jump(address) => {
                 self.program_counter = address;
             }
             // 2NNN - subroutine
-            (2, _, _, _) => {
-                let address = opcode & 0xFFF;
-
+            Instruction::Call(address) => {
+                if self.stack_pointer as usize >= STACK_SIZE {
+                    return Err(Chip8Error::StackOverflow {
+                        pc: self.instruction_pc,
+                    });
+                }
                 self.stack[self.stack_pointer as usize] = self.program_counter;
                 self.stack_pointer += 1;
 
                 self.program_counter = address;
             }
             // 3XNN
-            (3, _, _, _) => {
-                let x = second_digit as usize;
-                let nn = (opcode & 0xFF) as u8;
-
-                if self.vregs[x] == nn {
-                    self.program_counter += 2;
+            Instruction::SkipEqImm { x, nn } => {
+                if self.vregs[x as usize] == nn {
+                    self.skip_next();
                 }
             }
             // 4XNN
-            (4, _, _, _) => {
-                let x = second_digit as usize;
-                let nn = (opcode & 0xFF) as u8;
-
-                if self.vregs[x] != nn {
-                    self.program_counter += 2;
+            Instruction::SkipNeImm { x, nn } => {
+                if self.vregs[x as usize] != nn {
+                    self.skip_next();
                 }
             }
             // 5XY0
-            (5, _, _, 0) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-
-                if self.vregs[x] == self.vregs[y] {
-                    self.program_counter += 2;
+            Instruction::SkipEqReg { x, y } => {
+                if self.vregs[x as usize] == self.vregs[y as usize] {
+                    self.skip_next();
                 }
             }
-            // 5XY0
-            (9, _, _, 0) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-
-                if self.vregs[x] != self.vregs[y] {
-                    self.program_counter += 2;
+            // 5XY2 - XO-CHIP save VX..VY to memory at I
+            Instruction::SaveRange { x, y } => {
+                let i = self.ireg as usize;
+                for (offset, reg) in Self::register_range(x, y).enumerate() {
+                    self.write_ram(i + offset, self.vregs[reg])?;
+                }
+            }
+            // 5XY3 - XO-CHIP load VX..VY from memory at I
+            Instruction::LoadRange { x, y } => {
+                let i = self.ireg as usize;
+                for (offset, reg) in Self::register_range(x, y).enumerate() {
+                    self.vregs[reg] = self.read_ram(i + offset)?;
+                }
+            }
+            // 9XY0
+            Instruction::SkipNeReg { x, y } => {
+                if self.vregs[x as usize] != self.vregs[y as usize] {
+                    self.skip_next();
                 }
             }
             // set vreg NN to X
-            (6, _, _, _) => {
-                let value = opcode & 0xFF;
-                self.vregs[second_digit as usize] = value as u8;
+            Instruction::LoadImm { x, nn } => {
+                self.vregs[x as usize] = nn;
             }
             // add vreg NN by X
-            (7, _, _, _) => {
-                let x = second_digit as usize;
-                let nn = (opcode & 0xFF) as u8;
+            Instruction::AddImm { x, nn } => {
+                let x = x as usize;
                 self.vregs[x] = self.vregs[x].wrapping_add(nn);
             }
             // 8XY0
-            (8, _, _, 0) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-                self.vregs[x] = self.vregs[y];
+            Instruction::Move { x, y } => {
+                self.vregs[x as usize] = self.vregs[y as usize];
             }
             // 8XY1
-            (8, _, _, 1) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-                self.vregs[x] = self.vregs[x] | self.vregs[y];
-            }
-            // 8XY2
-            (8, _, _, 2) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-                self.vregs[x] = self.vregs[x] & self.vregs[y];
-            }
-            // 8XY3
-            (8, _, _, 3) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-                self.vregs[x] = self.vregs[x] ^ self.vregs[y];
-            }
-            // 8XY4
-            (8, _, _, 4) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-                let sum = self.vregs[x] as u16 + self.vregs[y] as u16;
-                if sum > 0xFF {
-                    self.vregs[0xF] = 1;
-                } else {
+            Instruction::Or { x, y } => {
+                self.vregs[x as usize] |= self.vregs[y as usize];
+                if self.quirks.logic_resets_vf {
                     self.vregs[0xF] = 0;
                 }
-                self.vregs[x] = self.vregs[x].wrapping_add(self.vregs[y]);
             }
-            // 8XY5
-            (8, _, _, 5) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-
-                // its opposite than usual but thats what its supposed to be
-                if self.vregs[x] > self.vregs[y] {
-                    self.vregs[0xF] = 1;
-                } else {
+            // 8XY2
+            Instruction::And { x, y } => {
+                self.vregs[x as usize] &= self.vregs[y as usize];
+                if self.quirks.logic_resets_vf {
                     self.vregs[0xF] = 0;
                 }
-                self.vregs[x] = self.vregs[x].wrapping_sub(self.vregs[y]);
             }
-            // 8XY7
-            (8, _, _, 7) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-
-                // its opposite than usual but thats what its supposed to be
-                if self.vregs[y] > self.vregs[x] {
-                    self.vregs[0xF] = 1;
-                } else {
+            // 8XY3
+            Instruction::Xor { x, y } => {
+                self.vregs[x as usize] ^= self.vregs[y as usize];
+                if self.quirks.logic_resets_vf {
                     self.vregs[0xF] = 0;
                 }
-                self.vregs[x] = self.vregs[y].wrapping_sub(self.vregs[x]);
             }
-            // 8XY6
-            (8, _, _, 6) => {
-                let x = second_digit as usize;
-                let mut value = self.vregs[x];
+            // 8XY4 - VF is the carry. the result is stored before the flag so
+            // that VF as X ends up holding the flag, as on the VIP
+            Instruction::Add { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (sum, carry) = self.vregs[x].overflowing_add(self.vregs[y]);
+                self.vregs[x] = sum;
+                self.vregs[0xF] = carry as u8;
+            }
+            // 8XY5 - VF is 1 when there's no borrow, including VX == VY
+            Instruction::Sub { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (diff, borrow) = self.vregs[x].overflowing_sub(self.vregs[y]);
+                self.vregs[x] = diff;
+                self.vregs[0xF] = !borrow as u8;
+            }
+            // 8XY7 - VX = VY - VX, VF as for 8XY5
+            Instruction::SubN { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let (diff, borrow) = self.vregs[y].overflowing_sub(self.vregs[x]);
+                self.vregs[x] = diff;
+                self.vregs[0xF] = !borrow as u8;
+            }
+            // 8XY6 - VF is the bit shifted out
+            Instruction::ShiftRight { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let value = if self.quirks.shift_uses_vy {
+                    self.vregs[y]
+                } else {
+                    self.vregs[x]
+                };
+                self.vregs[x] = value >> 1;
                 self.vregs[0xF] = value & 0x1;
-                value >>= 1;
-                self.vregs[x] = value;
             }
-            // 8XYE
-            (8, _, _, 0xE) => {
-                let x = second_digit as usize;
-                let mut value = self.vregs[x];
-                self.vregs[0xF] = value & 0x1;
-                value <<= 1;
-                self.vregs[x] = value;
+            // 8XYE - VF is the bit shifted out
+            Instruction::ShiftLeft { x, y } => {
+                let (x, y) = (x as usize, y as usize);
+                let value = if self.quirks.shift_uses_vy {
+                    self.vregs[y]
+                } else {
+                    self.vregs[x]
+                };
+                self.vregs[x] = value << 1;
+                self.vregs[0xF] = value >> 7;
             }
             // set ireg
-            (0xA, _, _, _) => {
-                let value = opcode & 0xFFF;
-                self.ireg = value;
+            Instruction::LoadI(address) => {
+                self.ireg = address;
             }
-            // BNNN variation (not BXNN - so it wont support those ROM's)
-            (0xB, _, _, _) => {
-                let nnn = opcode & 0xFFF;
-                self.program_counter = (self.vregs[0] as u16) + nnn;
+            // BNNN, or BXNN under the jump_uses_vx quirk
+            Instruction::JumpOffset { x, nnn } => {
+                let offset = if self.quirks.jump_uses_vx {
+                    self.vregs[x as usize]
+                } else {
+                    self.vregs[0]
+                };
+                self.program_counter = (offset as u16) + nnn;
             }
             // CXNN
-            (0xC, _, _, _) => {
-                let x = second_digit as usize;
-                let nn = (opcode & 0xFF) as u8;
-                let rng: u8 = rand::thread_rng().gen();
-                self.vregs[x] = rng & nn;
-            }
-            // display dxyn
-            (0xD, _, _, _) => {
-                let x = second_digit as usize;
-                let y = third_digit as usize;
-                let n = fourth_digit as u8;
-
-                // set coords
-                let x_coord = self.vregs[x] % 64;
-                let y_coord = self.vregs[y] % 32;
-
-                // set f register to 0 and display flag to false
-                self.vregs[0xF] = 0;
-                self.display_flag = false;
-
-                let mut flipped: bool = false;
-
-                for _y in 0..n {
-                    let addr = self.ireg + _y as u16;
-                    let pixels = self.ram[addr as usize];
-
-                    for _x in 0..8 {
-                        // fetch current pixel's bit. only flip on 1
-                        if (pixels & (0b1000_0000 >> _x)) != 0 {
-                            let x = (x_coord + _x) as usize % DISPLAY_WIDTH;
-                            let y = (y_coord + _y) as usize % DISPLAY_HEIGHT;
-
-                            let idx = x + DISPLAY_WIDTH * y;
-
-                            flipped |= self.display[idx];
-                            self.display[idx] ^= true;
-                        }
-                    }
-                }
-
-                if flipped {
-                    self.vregs[0xF] = 1;
-                    self.display_flag = true;
+            Instruction::Random { x, nn } => {
+                self.vregs[x as usize] = self.rng.next_byte() & nn;
+            }
+            // display dxyn, DXY0 draws a SUPER-CHIP 16x16 sprite
+            Instruction::Draw { x, y, n } => {
+                let x = self.vregs[x as usize];
+                let y = self.vregs[y as usize];
+                if n == 0 {
+                    self.draw_sprite(x, y, 16, 16)?;
                 } else {
-                    self.vregs[0xF] = 0;
-                    self.display_flag = false;
+                    self.draw_sprite(x, y, 8, n as usize)?;
                 }
             }
-            (0xE, _, 9, 0xE) => {
-                let x = second_digit as usize;
-                let vx = self.vregs[x];
-                let key = self.keyboard[vx as usize];
-                if key {
-                    self.program_counter += 2;
+            // SKIP KEY PRESS
+            Instruction::SkipKey { x } => {
+                if self.key_state(self.vregs[x as usize])? {
+                    self.skip_next();
                 }
             }
             // SKIP KEY RELEASE
-            (0xE, _, 0xA, 1) => {
-                let x = second_digit as usize;
-                let vx = self.vregs[x];
-                let key = self.keyboard[vx as usize];
-                if !key {
-                    self.program_counter += 2;
+            Instruction::SkipNotKey { x } => {
+                if !self.key_state(self.vregs[x as usize])? {
+                    self.skip_next();
+                }
+            }
+            // F000 NNNN - XO-CHIP load I with the following 16 bit word
+            Instruction::LoadLongI => {
+                self.ireg = self.fetch_opcode()?;
+            }
+            // FN01 - XO-CHIP select the drawing planes
+            Instruction::Planes(n) => {
+                self.planes = n & 0x3;
+            }
+            // F002 - XO-CHIP load the audio pattern buffer from I
+            Instruction::LoadAudio => {
+                let i = self.ireg as usize;
+                for idx in 0..AUDIO_PATTERN_SIZE {
+                    self.audio_pattern[idx] = self.read_ram(i + idx)?;
                 }
             }
+            // FX3A - XO-CHIP set the audio pitch
+            Instruction::Pitch { x } => {
+                self.pitch = self.vregs[x as usize];
+            }
             // FX07
-            (0xF, _, 0, 7) => {
-                let x = second_digit as usize;
-
-                self.vregs[x] = self.delay_timer;
-            }
-            //  FX0A
-            (0xF, _, 0, 0xA) => {
-                let x = second_digit as usize;
-                let mut pressed = false;
-                for i in 0..self.keyboard.len() {
-                    if self.keyboard[i] {
-                        self.vregs[x] = i as u8;
-                        pressed = true;
-                        break;
-                    }
+            Instruction::GetDelay { x } => {
+                self.vregs[x as usize] = self.delay_timer;
+            }
+            // FX0A - wait for a key to be pressed and released, as on the
+            // COSMAC VIP
+            Instruction::WaitKey { x } => match self.key_wait {
+                Some(key) if !self.keyboard[key as usize] => {
+                    self.vregs[x as usize] = key;
+                    self.key_wait = None;
                 }
-
-                if !pressed {
-                    self.program_counter -= 2;
+                _ => {
+                    if self.key_wait.is_none() {
+                        self.key_wait = self
+                            .keyboard
+                            .iter()
+                            .position(|&held| held)
+                            .map(|key| key as u8);
+                    }
+                    self.program_counter = self.program_counter.wrapping_sub(2);
                 }
-            }
+            },
             // FX15
-            (0xF, _, 1, 5) => {
-                let x = second_digit as usize;
-
-                self.delay_timer = self.vregs[x];
+            Instruction::SetDelay { x } => {
+                self.delay_timer = self.vregs[x as usize];
             }
             // FX18
-            (0xF, _, 1, 8) => {
-                let x = second_digit as usize;
-
-                self.sound_timer = self.vregs[x];
+            Instruction::SetSound { x } => {
+                self.sound_timer = self.vregs[x as usize];
             }
             // FX1E
-            (0xF, _, 1, 0xE) => {
-                let x = second_digit as usize;
-
-                self.ireg = self.ireg.wrapping_add(self.vregs[x].into());
+            Instruction::AddI { x } => {
+                self.ireg = self.ireg.wrapping_add(self.vregs[x as usize].into());
             }
-            // FX28
-            (0xF, _, 2, 9) => {
-                let x = second_digit as usize;
-                let c = self.vregs[x] as u16;
-                self.ireg = c * 5;
+            // FX29 - point I at the font glyph for the low nibble of VX
+            Instruction::Font { x } => {
+                let c = (self.vregs[x as usize] & 0xF) as u16;
+                self.ireg = self.font_base + c * 5;
+            }
+            // FX30 - point I at the big font glyph for VX
+            Instruction::BigFont { x } => {
+                let c = (self.vregs[x as usize] & 0xF) as u16;
+                self.ireg = self.big_font_addr() + c * 10;
             }
             // FX33
-            (0xF, _, 3, 3) => {
-                let x = second_digit as usize;
+            Instruction::Bcd { x } => {
                 let mut split: [u8; 3] = [0, 0, 0];
-                let vx = self.vregs[x];
-                println!("VX: {}", vx);
+                let vx = self.vregs[x as usize];
                 let mut vx_modifiable = vx;
                 if vx >= 200 {
                     split[0] = 2;
@@ -315,30 +327,166 @@ impl Chip8 {
                 split[1] = vx_modifiable / 10;
 
                 let ireg = self.ireg as usize;
-                self.ram[ireg] = split[0];
-                self.ram[ireg + 1] = split[1];
-                self.ram[ireg + 2] = split[2];
+                for (offset, digit) in split.iter().enumerate() {
+                    self.write_ram(ireg + offset, *digit)?;
+                }
             }
             // FX55
-            (0xF, _, 5, 5) => {
-                let x = second_digit as usize;
+            Instruction::Store { x } => {
+                let x = x as usize;
                 let i = self.ireg as usize;
                 for idx in 0..=x {
-                    self.ram[i + idx] = self.vregs[idx];
+                    self.write_ram(i + idx, self.vregs[idx])?;
+                }
+
+                if self.quirks.load_store_inc_i {
+                    self.ireg = self.ireg.wrapping_add(x as u16 + 1);
                 }
             }
             // FX65
-            (0xF, _, 6, 5) => {
-                let x = second_digit as usize;
+            Instruction::Load { x } => {
+                let x = x as usize;
                 let i = self.ireg as usize;
                 for idx in 0..=x {
-                    self.vregs[idx] = self.ram[i + idx];
+                    self.vregs[idx] = self.read_ram(i + idx)?;
+                }
+
+                if self.quirks.load_store_inc_i {
+                    self.ireg = self.ireg.wrapping_add(x as u16 + 1);
+                }
+            }
+            // FX75 - save V0..VX to the RPL user flags
+            Instruction::SaveFlags { x } => {
+                let x = (x as usize).min(RPL_SIZE - 1);
+                self.rpl[..=x].copy_from_slice(&self.vregs[..=x]);
+            }
+            // FX85 - restore V0..VX from the RPL user flags
+            Instruction::LoadFlags { x } => {
+                let x = (x as usize).min(RPL_SIZE - 1);
+                self.vregs[..=x].copy_from_slice(&self.rpl[..=x]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// XORs a sprite from I onto the display at (x, y). `width` is 8 for
+    /// regular sprites and 16 for SUPER-CHIP ones, which use two bytes a row.
+    /// With both XO-CHIP planes selected the second plane's sprite data
+    /// follows straight after the first's.
+    fn draw_sprite(&mut self, x: u8, y: u8, width: usize, rows: usize) -> Result<(), Chip8Error> {
+        let display_width = self.display_width();
+        let display_height = self.display_height();
+        let bytes_per_row = width / 8;
+
+        // set coords
+        let x_coord = x as usize % display_width;
+        let y_coord = y as usize % display_height;
+
+        // set f register to 0 and display flag to false
+        self.vregs[0xF] = 0;
+        self.display_flag = false;
+
+        let mut flipped: bool = false;
+        let mut addr = self.ireg as usize;
+
+        for plane in [0b01u8, 0b10] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            for _y in 0..rows {
+                let mut pixels: u16 = 0;
+                for _ in 0..bytes_per_row {
+                    pixels = (pixels << 8) | self.read_ram(addr)? as u16;
+                    addr += 1;
+                }
+
+                for _x in 0..width {
+                    // fetch current pixel's bit. only flip on 1
+                    if (pixels & (1 << (width - 1 - _x))) != 0 {
+                        let x = x_coord + _x;
+                        let y = y_coord + _y;
+
+                        if self.quirks.clip_sprites && (x >= display_width || y >= display_height) {
+                            continue;
+                        }
+
+                        let x = x % display_width;
+                        let y = y % display_height;
+
+                        let idx = x + display_width * y;
+
+                        flipped |= self.display[idx] & plane != 0;
+                        self.display[idx] ^= plane;
+                    }
                 }
             }
-            (_, _, _, _) => {
-                println!("Unimplemented opcode: {:#04x}", opcode)
-                // unimplemented!("Unimplemented opcode: {:#04x}", opcode)
+        }
+
+        if flipped {
+            self.vregs[0xF] = 1;
+            self.display_flag = true;
+        } else {
+            self.vregs[0xF] = 0;
+            self.display_flag = false;
+        }
+
+        if self.quirks.display_wait {
+            self.vblank_wait = true;
+        }
+
+        Ok(())
+    }
+
+    /// Shifts the selected planes by (dx, dy) pixels, filling with blank pixels.
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let width = self.display_width() as isize;
+        let height = self.display_height() as isize;
+        let old = self.display.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = (x - dx, y - dy);
+                let inside = src_x >= 0 && src_x < width && src_y >= 0 && src_y < height;
+                let moved = if inside {
+                    old[(src_x + src_y * width) as usize] & self.planes
+                } else {
+                    0
+                };
+
+                // only the selected planes move, the others stay put
+                let idx = (x + y * width) as usize;
+                self.display[idx] = (self.display[idx] & !self.planes) | moved;
             }
         }
+        self.display_flag = true;
+    }
+
+    /// Skips the next instruction, which is four bytes long if it's an
+    /// XO-CHIP F000 NNNN long load.
+    fn skip_next(&mut self) {
+        if self.mode == Mode::XoChip && self.peek_word(self.program_counter) == Ok(0xF000) {
+            self.program_counter = self.program_counter.wrapping_add(4);
+        } else {
+            self.program_counter = self.program_counter.wrapping_add(2);
+        }
+    }
+
+    fn key_state(&self, key: u8) -> Result<bool, Chip8Error> {
+        self.keyboard
+            .get(key as usize)
+            .copied()
+            .ok_or(Chip8Error::InvalidKey { key: key as usize })
+    }
+
+    /// Register indices from X to Y inclusive, walking backwards if Y < X.
+    fn register_range(x: u8, y: u8) -> Box<dyn Iterator<Item = usize>> {
+        let (x, y) = (x as usize, y as usize);
+        if x <= y {
+            Box::new(x..=y)
+        } else {
+            Box::new((y..=x).rev())
+        }
     }
 }
@@ -1,7 +1,36 @@
 pub mod constants;
 
+pub mod asm;
+
 pub mod chip8;
 
+pub mod debugger;
+
+pub mod decode;
+
+pub mod disasm;
+
+pub mod dump;
+
+pub mod error;
+
 pub mod instructions;
 
-pub mod tests;
+pub mod mode;
+
+pub mod movie;
+
+pub mod quirks;
+
+pub mod rewind;
+
+pub mod rng;
+
+pub mod scheduler;
+
+pub mod state;
+
+pub mod trace;
+
+#[cfg(test)]
+mod tests;
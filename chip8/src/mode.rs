@@ -0,0 +1,28 @@
+use crate::constants::{RAM_SIZE, XO_RAM_SIZE};
+
+/// Which machine the interpreter emulates. XO-CHIP extends SUPER-CHIP with
+/// 64K of RAM, a second display plane, programmable audio and a few opcodes
+/// that change the instruction length, so it has to be opted into.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Classic,
+    XoChip,
+}
+
+impl Mode {
+    pub fn ram_size(&self) -> usize {
+        match self {
+            Mode::Classic => RAM_SIZE,
+            Mode::XoChip => XO_RAM_SIZE,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "classic" | "chip8" | "schip" => Some(Mode::Classic),
+            "xo" | "xochip" | "xo-chip" => Some(Mode::XoChip),
+            _ => None,
+        }
+    }
+}
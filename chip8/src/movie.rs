@@ -0,0 +1,343 @@
+/*
+MOVIE FORMAT (for reference):
+
+All multi-byte values are big endian.
+
+  OFFSET  SIZE          CONTENT
+  ~~~~~~  ~~~~          ~~~~~~~
+       0     4          magic "C8MV"
+       4     1          format version
+       5     8          FNV-1a hash of the ROM
+      13     1          mode (0 classic, 1 XO-CHIP)
+      14     1          quirks, bits as in save states
+      15     8          RNG seed
+      23     8          instructions per second
+      31     2          font base address
+      33     4          frames recorded
+      37     4          event count N
+      41     ?          N events
+
+Events are in the order they happen. Each starts with the frame number and
+a tag. Key events come before the frame they're numbered with runs, state
+hashes are taken after the frame before it ran:
+
+  SIZE  CONTENT
+  ~~~~  ~~~~~~~
+     4  frame
+     1  0 for a key event
+     1  key 0-F
+     1  1 pressed, 0 released
+
+     4  frame
+     1  1 for a state hash
+     8  FNV-1a hash of the save state
+*/
+
+use crate::chip8::Chip8;
+use crate::constants::MAX_FONT_BASE;
+use crate::error::Chip8Error;
+use crate::mode::Mode;
+use crate::quirks::Quirks;
+use crate::state::{quirks_from_bits, quirks_to_bits, Reader};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+pub const MOVIE_VERSION: u8 = 1;
+/// Frames between the state hashes playback checks against, once a second.
+pub const HASH_INTERVAL: u32 = 60;
+
+/// 64-bit FNV-1a, for telling ROMs and machine states apart.
+pub fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xCBF2_9CE4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01B3)
+    })
+}
+
+/// Everything needed to set up the machine the movie was recorded on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MovieHeader {
+    pub rom_hash: u64,
+    pub mode: Mode,
+    pub quirks: Quirks,
+    pub seed: u64,
+    pub ips: u64,
+    pub font_base: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovieEvent {
+    Key { frame: u32, key: u8, pressed: bool },
+    Hash { frame: u32, hash: u64 },
+}
+
+impl MovieEvent {
+    pub fn frame(&self) -> u32 {
+        match *self {
+            MovieEvent::Key { frame, .. } | MovieEvent::Hash { frame, .. } => frame,
+        }
+    }
+}
+
+/// A recorded play session: the machine it started from and every key the
+/// player pressed, frame by frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Movie {
+    pub header: MovieHeader,
+    pub frames: u32,
+    pub events: Vec<MovieEvent>,
+}
+
+impl Movie {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let header = &self.header;
+        let mut out = Vec::with_capacity(41 + self.events.len() * 13);
+
+        out.extend_from_slice(MAGIC);
+        out.push(MOVIE_VERSION);
+        out.extend_from_slice(&header.rom_hash.to_be_bytes());
+        out.push(match header.mode {
+            Mode::Classic => 0,
+            Mode::XoChip => 1,
+        });
+        out.push(quirks_to_bits(&header.quirks));
+        out.extend_from_slice(&header.seed.to_be_bytes());
+        out.extend_from_slice(&header.ips.to_be_bytes());
+        out.extend_from_slice(&header.font_base.to_be_bytes());
+        out.extend_from_slice(&self.frames.to_be_bytes());
+        out.extend_from_slice(&(self.events.len() as u32).to_be_bytes());
+
+        for event in &self.events {
+            out.extend_from_slice(&event.frame().to_be_bytes());
+            match *event {
+                MovieEvent::Key { key, pressed, .. } => {
+                    out.extend_from_slice(&[0, key, pressed as u8])
+                }
+                MovieEvent::Hash { hash, .. } => {
+                    out.push(1);
+                    out.extend_from_slice(&hash.to_be_bytes());
+                }
+            }
+        }
+        out
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Movie, Chip8Error> {
+        let mut reader = Reader::new(data, invalid);
+
+        if reader.bytes(4)? != MAGIC {
+            return Err(invalid("not a movie"));
+        }
+        let version = reader.u8()?;
+        if version != MOVIE_VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+
+        let rom_hash = reader.u64()?;
+        let mode = match reader.u8()? {
+            0 => Mode::Classic,
+            1 => Mode::XoChip,
+            _ => return Err(invalid("unknown mode")),
+        };
+        let header = MovieHeader {
+            rom_hash,
+            mode,
+            quirks: quirks_from_bits(reader.u8()?),
+            seed: reader.u64()?,
+            ips: reader.u64()?,
+            font_base: reader.u16()?,
+        };
+        if header.font_base > MAX_FONT_BASE {
+            return Err(invalid("font base out of range"));
+        }
+        let frames = reader.u32()?;
+
+        let count = reader.u32()?;
+        let mut events = Vec::new();
+        for _ in 0..count {
+            let frame = reader.u32()?;
+            let event = match reader.u8()? {
+                0 => {
+                    let key = reader.u8()?;
+                    if key > 0xF {
+                        return Err(invalid("key out of range"));
+                    }
+                    MovieEvent::Key {
+                        frame,
+                        key,
+                        pressed: reader.u8()? != 0,
+                    }
+                }
+                1 => MovieEvent::Hash {
+                    frame,
+                    hash: reader.u64()?,
+                },
+                _ => return Err(invalid("unknown event")),
+            };
+            if events
+                .last()
+                .is_some_and(|last: &MovieEvent| last.frame() > frame)
+            {
+                return Err(invalid("events out of order"));
+            }
+            events.push(event);
+        }
+
+        Ok(Movie {
+            header,
+            frames,
+            events,
+        })
+    }
+
+    /// Fails unless `rom` is the ROM the movie was recorded with.
+    pub fn check_rom(&self, rom: &[u8]) -> Result<(), Chip8Error> {
+        if fnv1a(rom) != self.header.rom_hash {
+            return Err(invalid("recorded with a different ROM"));
+        }
+        Ok(())
+    }
+}
+
+fn invalid(reason: &str) -> Chip8Error {
+    Chip8Error::InvalidMovie {
+        reason: reason.to_string(),
+    }
+}
+
+fn state_hash(chip8: &Chip8) -> u64 {
+    fnv1a(&chip8.save_state())
+}
+
+/// Records a movie. Send every key through `keypress` instead of
+/// `Chip8::keypress` and call `end_frame` after each frame runs.
+pub struct MovieRecorder {
+    movie: Movie,
+}
+
+impl MovieRecorder {
+    /// Starts recording `chip8`, which should have `rom` loaded and not have
+    /// run yet. The state of its RNG is taken as the seed.
+    pub fn new(chip8: &Chip8, rom: &[u8], ips: u64) -> Self {
+        MovieRecorder {
+            movie: Movie {
+                header: MovieHeader {
+                    rom_hash: fnv1a(rom),
+                    mode: chip8.mode,
+                    quirks: chip8.quirks,
+                    seed: chip8.rng.state().unwrap_or(0),
+                    ips,
+                    font_base: chip8.font_base,
+                },
+                frames: 0,
+                events: Vec::new(),
+            },
+        }
+    }
+
+    /// Presses or releases a key, recording it if that changes anything.
+    pub fn keypress(
+        &mut self,
+        chip8: &mut Chip8,
+        key: usize,
+        pressed: bool,
+    ) -> Result<(), Chip8Error> {
+        let changed = chip8.keyboard.get(key).is_some_and(|&held| held != pressed);
+        chip8.keypress(key, pressed)?;
+        if changed {
+            self.movie.events.push(MovieEvent::Key {
+                frame: self.movie.frames,
+                key: key as u8,
+                pressed,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn end_frame(&mut self, chip8: &Chip8) {
+        self.movie.frames += 1;
+        if self.movie.frames.is_multiple_of(HASH_INTERVAL) {
+            self.push_hash(chip8);
+        }
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.movie.frames
+    }
+
+    /// The finished movie. The final state is hashed too, so playback
+    /// checks where it ends up.
+    pub fn finish(mut self, chip8: &Chip8) -> Movie {
+        if !self.movie.frames.is_multiple_of(HASH_INTERVAL) {
+            self.push_hash(chip8);
+        }
+        self.movie
+    }
+
+    fn push_hash(&mut self, chip8: &Chip8) {
+        self.movie.events.push(MovieEvent::Hash {
+            frame: self.movie.frames,
+            hash: state_hash(chip8),
+        });
+    }
+}
+
+/// Plays a movie back on a machine set up from its header. Call
+/// `begin_frame` before each frame runs and `end_frame` after.
+pub struct MoviePlayer {
+    movie: Movie,
+    frame: u32,
+    next: usize,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie) -> Self {
+        MoviePlayer {
+            movie,
+            frame: 0,
+            next: 0,
+        }
+    }
+
+    /// Presses and releases the keys recorded for the coming frame.
+    pub fn begin_frame(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        while let Some(&event) = self.movie.events.get(self.next) {
+            match event {
+                MovieEvent::Key {
+                    frame,
+                    key,
+                    pressed,
+                } if frame <= self.frame => chip8.keypress(key as usize, pressed)?,
+                // hashes are checked in end_frame
+                MovieEvent::Hash { frame, .. } if frame <= self.frame => {}
+                _ => break,
+            }
+            self.next += 1;
+        }
+        Ok(())
+    }
+
+    /// Checks the machine against the recording, failing with
+    /// `MovieDesync` if it has drifted.
+    pub fn end_frame(&mut self, chip8: &Chip8) -> Result<(), Chip8Error> {
+        self.frame += 1;
+        while let Some(&event) = self.movie.events.get(self.next) {
+            match event {
+                MovieEvent::Hash { frame, hash } if frame == self.frame => {
+                    self.next += 1;
+                    if state_hash(chip8) != hash {
+                        return Err(Chip8Error::MovieDesync { frame });
+                    }
+                }
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.movie.frames
+    }
+}
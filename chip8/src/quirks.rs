@@ -0,0 +1,91 @@
+/*
+QUIRKS (for reference):
+
+Several opcodes were interpreted differently by the original COSMAC VIP
+interpreter, by CHIP-48 on the HP48 calculators and by SUPER-CHIP. ROMs tend
+to depend on the behaviour of whatever interpreter they were written for, so
+each ambiguity is exposed as a switch here.
+
+  QUIRK               OPCODES         ON                          OFF
+  ~~~~~               ~~~~~~~         ~~                          ~~~
+  shift_uses_vy       8XY6 / 8XYE     VX = VY shifted             VX shifted in place
+  jump_uses_vx        BNNN            jump to XNN + VX            jump to NNN + V0
+  load_store_inc_i    FX55 / FX65     I = I + X + 1 afterwards    I left unchanged
+  logic_resets_vf     8XY1/2/3        VF = 0 afterwards           VF left untouched
+  clip_sprites        DXYN            pixels past the edge cut    pixels wrap around
+  display_wait        DXYN            wait for vblank after draw  draw immediately
+*/
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    pub shift_uses_vy: bool,
+    pub jump_uses_vx: bool,
+    pub load_store_inc_i: bool,
+    pub logic_resets_vf: bool,
+    pub clip_sprites: bool,
+    pub display_wait: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter.
+    pub fn cosmac_vip() -> Self {
+        Quirks {
+            shift_uses_vy: true,
+            jump_uses_vx: false,
+            load_store_inc_i: true,
+            logic_resets_vf: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+
+    /// CHIP-48 on the HP48 calculators.
+    pub fn chip48() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            jump_uses_vx: true,
+            load_store_inc_i: true,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1.
+    pub fn schip() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            jump_uses_vx: true,
+            load_store_inc_i: false,
+            logic_resets_vf: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    /// Looks a preset up by name, e.g. from a command line flag.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "default" => Some(Quirks::default()),
+            "vip" | "cosmac" | "cosmac-vip" | "chip8" => Some(Quirks::cosmac_vip()),
+            "chip48" | "chip-48" => Some(Quirks::chip48()),
+            "schip" | "superchip" | "super-chip" => Some(Quirks::schip()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    /// The behaviour this interpreter has always had, kept so existing ROMs
+    /// keep running the way they did.
+    fn default() -> Self {
+        Quirks {
+            shift_uses_vy: false,
+            jump_uses_vx: false,
+            load_store_inc_i: false,
+            logic_resets_vf: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::VecDeque;
+
+use crate::chip8::Chip8;
+
+/// About ten seconds of frames, and a memory budget that fits that many
+/// XO-CHIP frames several times over.
+pub const DEFAULT_REWIND_FRAMES: usize = 600;
+pub const DEFAULT_REWIND_BYTES: usize = 16 * 1024 * 1024;
+
+// how a snapshot differs from the one after it: the XOR of the two with
+// runs of zeros squeezed out, see `compress`
+struct Delta {
+    len: usize,
+    data: Vec<u8>,
+}
+
+/// A history of save states for running the machine backwards. Only the
+/// newest snapshot is kept whole; every older one is stored as its
+/// difference from the next, which between consecutive frames is mostly
+/// nothing.
+pub struct Rewind {
+    latest: Option<Vec<u8>>,
+    deltas: VecDeque<Delta>,
+    max_frames: usize,
+    max_bytes: usize,
+    bytes: usize,
+}
+
+impl Rewind {
+    /// Keeps at most `max_frames` frames of history, and fewer if they
+    /// take more than `max_bytes` of memory.
+    pub fn new(max_frames: usize, max_bytes: usize) -> Self {
+        Rewind {
+            latest: None,
+            deltas: VecDeque::new(),
+            max_frames,
+            max_bytes,
+            bytes: 0,
+        }
+    }
+
+    /// Records the machine as it is now. Call once per frame.
+    pub fn push(&mut self, chip8: &Chip8) {
+        let state = chip8.save_state();
+        if let Some(latest) = self.latest.take() {
+            let delta = Delta {
+                len: latest.len(),
+                data: compress(&xor(&latest, &state)),
+            };
+            self.bytes += delta.data.len();
+            self.deltas.push_back(delta);
+        }
+        self.latest = Some(state);
+
+        while self.deltas.len() > self.max_frames
+            || (self.bytes + self.latest_len() > self.max_bytes && !self.deltas.is_empty())
+        {
+            let oldest = self.deltas.pop_front().unwrap();
+            self.bytes -= oldest.data.len();
+        }
+    }
+
+    /// Puts the machine back one frame. Returns false once the history has
+    /// run out.
+    pub fn step_back(&mut self, chip8: &mut Chip8) -> bool {
+        let (Some(latest), Some(delta)) = (self.latest.as_ref(), self.deltas.pop_back()) else {
+            return false;
+        };
+        self.bytes -= delta.data.len();
+
+        let mut previous = xor(
+            latest,
+            &decompress(&delta.data, latest.len().max(delta.len)),
+        );
+        previous.truncate(delta.len);
+        let restored = chip8.load_state(&previous).is_ok();
+        self.latest = Some(previous);
+        restored
+    }
+
+    /// Frames that can be stepped back.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    /// Bytes held by the history, newest snapshot included.
+    pub fn memory_used(&self) -> usize {
+        self.bytes + self.latest_len()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+        self.bytes = 0;
+    }
+
+    fn latest_len(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len)
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Rewind::new(DEFAULT_REWIND_FRAMES, DEFAULT_REWIND_BYTES)
+    }
+}
+
+// snapshots change length when the resolution does, the shorter one reads
+// as zero padded
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    (0..a.len().max(b.len()))
+        .map(|i| a.get(i).unwrap_or(&0) ^ b.get(i).unwrap_or(&0))
+        .collect()
+}
+
+/*
+DELTA ENCODING (for reference):
+
+A sequence of runs, each a count of zero bytes then a count of literal
+bytes followed by the literals themselves. Counts are LEB128 varints, seven
+bits a byte with the top bit set on all but the last. Trailing zeros are
+left off and come back when decompressing to a known length.
+
+  00 00 00 00 07 01 00 00   ->   04 02 07 01
+*/
+fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let zeros = data[pos..].iter().take_while(|&&b| b == 0).count();
+        pos += zeros;
+        if pos == data.len() {
+            break;
+        }
+        let literals = data[pos..].iter().take_while(|&&b| b != 0).count();
+        push_varint(&mut out, zeros);
+        push_varint(&mut out, literals);
+        out.extend_from_slice(&data[pos..pos + literals]);
+        pos += literals;
+    }
+    out
+}
+
+fn decompress(data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let zeros = read_varint(data, &mut pos);
+        let literals = read_varint(data, &mut pos);
+        out.resize(out.len() + zeros, 0);
+        out.extend_from_slice(&data[pos..pos + literals]);
+        pos += literals;
+    }
+    out.resize(len, 0);
+    out
+}
+
+fn push_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
@@ -0,0 +1,74 @@
+/// Where CXNN gets its random bytes from. Implement this to feed a ROM a
+/// known sequence, e.g. in tests.
+pub trait RandomSource {
+    fn next_byte(&mut self) -> u8;
+
+    /// The generator's position, if it has one that fits in a `u64`. Save
+    /// states store it so a restored machine draws the same bytes again.
+    fn state(&self) -> Option<u64> {
+        None
+    }
+
+    fn restore(&mut self, _state: u64) {}
+}
+
+/// SplitMix64. Two machines seeded alike draw identical bytes.
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        SeededRng { state: seed }
+    }
+}
+
+impl RandomSource for SeededRng {
+    fn next_byte(&mut self) -> u8 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        (z ^ (z >> 31)) as u8
+    }
+
+    fn state(&self) -> Option<u64> {
+        Some(self.state)
+    }
+
+    fn restore(&mut self, state: u64) {
+        self.state = state;
+    }
+}
+
+/// Hands out the given bytes in order, starting over once they run out.
+pub struct ScriptedRng {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+impl ScriptedRng {
+    pub fn new(bytes: &[u8]) -> Self {
+        assert!(!bytes.is_empty(), "ScriptedRng needs at least one byte");
+        ScriptedRng {
+            bytes: bytes.to_vec(),
+            pos: 0,
+        }
+    }
+}
+
+impl RandomSource for ScriptedRng {
+    fn next_byte(&mut self) -> u8 {
+        let byte = self.bytes[self.pos];
+        self.pos = (self.pos + 1) % self.bytes.len();
+        byte
+    }
+
+    fn state(&self) -> Option<u64> {
+        Some(self.pos as u64)
+    }
+
+    fn restore(&mut self, state: u64) {
+        self.pos = state as usize % self.bytes.len();
+    }
+}
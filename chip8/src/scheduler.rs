@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use crate::chip8::Chip8;
+use crate::error::Chip8Error;
+
+/// Timers always count down at this rate, whatever the display refreshes at.
+pub const FRAME_RATE: u64 = 60;
+/// 10 instructions a frame, what the frontend has always run.
+pub const DEFAULT_IPS: u64 = 600;
+/// Frames run back to back at most when catching up, so a stall (a dragged
+/// window, a debugger break) doesn't fast-forward the game. The rest of the
+/// backlog is dropped. Scaled up along with the speed.
+pub const MAX_CATCH_UP_FRAMES: u32 = 4;
+
+/// Paces emulation against real time. Feed it the time elapsed since the
+/// last call to `advance` and run as many frames as it says are due.
+pub struct Scheduler {
+    ips: u64,
+    speed: f64,
+    accumulator: Duration,
+    frame: u64,
+}
+
+impl Scheduler {
+    pub fn new(ips: u64) -> Self {
+        Scheduler {
+            ips,
+            speed: 1.0,
+            accumulator: Duration::ZERO,
+            frame: 0,
+        }
+    }
+
+    pub fn ips(&self) -> u64 {
+        self.ips
+    }
+
+    pub fn set_ips(&mut self, ips: u64) {
+        self.ips = ips;
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    /// Runs frames `speed` times as often as real time says, e.g. 4.0 for
+    /// fast-forward or 0.25 for slow motion. Timers speed up with them.
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+    }
+
+    pub fn frame_time() -> Duration {
+        Duration::from_nanos(1_000_000_000 / FRAME_RATE)
+    }
+
+    /// Adds `elapsed` to the backlog and returns how many whole frames are
+    /// due, at most `MAX_CATCH_UP_FRAMES` times the speed.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        let frame_time = Self::frame_time();
+        let max_frames = MAX_CATCH_UP_FRAMES * self.speed.ceil().max(1.0) as u32;
+        self.accumulator += elapsed.mul_f64(self.speed);
+
+        let mut due = 0;
+        while self.accumulator >= frame_time {
+            self.accumulator -= frame_time;
+            due += 1;
+            if due == max_frames {
+                self.accumulator = self.accumulator.min(frame_time);
+                break;
+            }
+        }
+        due
+    }
+
+    /// How long until the next frame is due, for sleeping when there's
+    /// nothing to do yet.
+    pub fn until_next_frame(&self) -> Duration {
+        Self::frame_time()
+            .saturating_sub(self.accumulator)
+            .div_f64(self.speed)
+    }
+
+    /// Instructions to run in the next frame. IPS rarely divides evenly by
+    /// 60, so the remainder is spread over the second: 700 IPS alternates
+    /// 11 and 12 and always adds up to exactly 700 every 60 frames.
+    pub fn next_frame_cycles(&mut self) -> u64 {
+        let second_frame = self.frame % FRAME_RATE;
+        let cycles =
+            self.ips * (second_frame + 1) / FRAME_RATE - self.ips * second_frame / FRAME_RATE;
+        self.frame += 1;
+        cycles
+    }
+
+    /// Runs one frame: this frame's share of instructions, then a timer
+    /// tick. Stops early if the ROM exits.
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        for _ in 0..self.next_frame_cycles() {
+            if chip8.exited {
+                break;
+            }
+            chip8.tick()?;
+        }
+        chip8.tick_timers();
+        Ok(())
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new(DEFAULT_IPS)
+    }
+}
@@ -0,0 +1,306 @@
+/*
+SAVE STATE FORMAT (for reference):
+
+All multi-byte values are big endian. Version 4 layout. Version 3 stops
+after the font base and starts counting cycles from 0 again, version 2 also
+stops after the RNG state and had its fonts at 0x000, version 1 also has no
+RNG state:
+
+  OFFSET  SIZE          CONTENT
+  ~~~~~~  ~~~~          ~~~~~~~
+       0     4          magic "C8ST"
+       4     1          format version
+       5     1          mode (0 classic, 1 XO-CHIP)
+       6     1          quirks, bit 0 shift_uses_vy, 1 jump_uses_vx,
+                        2 load_store_inc_i, 3 logic_resets_vf,
+                        4 clip_sprites, 5 display_wait
+       7     1          flags, bit 0 hires, 1 exited, 2 vblank_wait,
+                        3 FX0A waiting for a key to be released,
+                        4-7 that key
+       8     1          selected XO-CHIP planes
+       9     1          error policy (0 halt, 1 ignore)
+      10     2          program counter
+      12     2          I
+      14     2          stack pointer
+      16     2          address of the instruction last executed
+      18     1          delay timer
+      19     1          sound timer
+      20     1          XO-CHIP pitch
+      21    16          V0-VF
+      37    32          stack, 16 words
+      69     2          keyboard, bit N set while key N is held
+      71    16          RPL user flags
+      87    16          XO-CHIP audio pattern
+     103     4          display length D
+     107     D          display, one byte per pixel
+   107+D     4          RAM length R
+   111+D     R          RAM
+ 111+D+R     1          1 if the RNG state follows, 0 if the RNG has none
+ 112+D+R     8          RNG state
+ 120+D+R     2          font base address
+ 122+D+R     8          instructions executed so far
+*/
+
+use crate::chip8::Chip8;
+use crate::constants::{
+    AUDIO_PATTERN_SIZE, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_DISPLAY_HEIGHT, HIRES_DISPLAY_WIDTH,
+    MAX_FONT_BASE, RPL_SIZE, STACK_SIZE, VREG_SIZE,
+};
+use crate::error::{Chip8Error, ErrorPolicy};
+use crate::mode::Mode;
+use crate::quirks::Quirks;
+
+const MAGIC: &[u8; 4] = b"C8ST";
+pub const STATE_VERSION: u8 = 4;
+
+impl Chip8 {
+    /// Snapshots the whole machine in the format documented above.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.ram.len() + self.display.len() + 128);
+
+        out.extend_from_slice(MAGIC);
+        out.push(STATE_VERSION);
+        out.push(match self.mode {
+            Mode::Classic => 0,
+            Mode::XoChip => 1,
+        });
+        out.push(quirks_to_bits(&self.quirks));
+        let key_wait = self.key_wait.map_or(0, |key| 0b1000 | key << 4);
+        out.push(
+            self.hires as u8 | (self.exited as u8) << 1 | (self.vblank_wait as u8) << 2 | key_wait,
+        );
+        out.push(self.planes);
+        out.push(match self.error_policy {
+            ErrorPolicy::Halt => 0,
+            ErrorPolicy::Ignore => 1,
+        });
+
+        for word in [
+            self.program_counter,
+            self.ireg,
+            self.stack_pointer,
+            self.instruction_pc,
+        ] {
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+        out.extend_from_slice(&[self.delay_timer, self.sound_timer, self.pitch]);
+        out.extend_from_slice(&self.vregs);
+        for word in self.stack {
+            out.extend_from_slice(&word.to_be_bytes());
+        }
+
+        let keys = self
+            .keyboard
+            .iter()
+            .enumerate()
+            .fold(0u16, |keys, (idx, &pressed)| keys | (pressed as u16) << idx);
+        out.extend_from_slice(&keys.to_be_bytes());
+        out.extend_from_slice(&self.rpl);
+        out.extend_from_slice(&self.audio_pattern);
+
+        out.extend_from_slice(&(self.display.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.display);
+        out.extend_from_slice(&(self.ram.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.ram);
+
+        let rng = self.rng.state();
+        out.push(rng.is_some() as u8);
+        out.extend_from_slice(&rng.unwrap_or(0).to_be_bytes());
+        out.extend_from_slice(&self.font_base.to_be_bytes());
+        out.extend_from_slice(&self.cycles.to_be_bytes());
+
+        out
+    }
+
+    /// Restores a snapshot taken by `save_state`. Nothing is changed unless
+    /// the whole snapshot is valid.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        let mut reader = Reader::new(data, invalid);
+
+        if reader.bytes(4)? != MAGIC {
+            return Err(invalid("not a save state"));
+        }
+        let version = reader.u8()?;
+        if version == 0 || version > STATE_VERSION {
+            return Err(invalid(&format!("unsupported version {}", version)));
+        }
+
+        let mode = match reader.u8()? {
+            0 => Mode::Classic,
+            1 => Mode::XoChip,
+            _ => return Err(invalid("unknown mode")),
+        };
+        let quirks = quirks_from_bits(reader.u8()?);
+        let flags = reader.u8()?;
+        let planes = reader.u8()?;
+        let error_policy = match reader.u8()? {
+            0 => ErrorPolicy::Halt,
+            1 => ErrorPolicy::Ignore,
+            _ => return Err(invalid("unknown error policy")),
+        };
+
+        let program_counter = reader.u16()?;
+        let ireg = reader.u16()?;
+        let stack_pointer = reader.u16()?;
+        let instruction_pc = reader.u16()?;
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+        let pitch = reader.u8()?;
+
+        let mut vregs = [0; VREG_SIZE];
+        vregs.copy_from_slice(reader.bytes(VREG_SIZE)?);
+        let mut stack = [0; STACK_SIZE];
+        for word in stack.iter_mut() {
+            *word = reader.u16()?;
+        }
+        if stack_pointer as usize > STACK_SIZE {
+            return Err(invalid("stack pointer out of range"));
+        }
+
+        let keys = reader.u16()?;
+        let mut rpl = [0; RPL_SIZE];
+        rpl.copy_from_slice(reader.bytes(RPL_SIZE)?);
+        let mut audio_pattern = [0; AUDIO_PATTERN_SIZE];
+        audio_pattern.copy_from_slice(reader.bytes(AUDIO_PATTERN_SIZE)?);
+
+        let hires = flags & 0b001 != 0;
+        let display_len = reader.u32()? as usize;
+        let display = reader.bytes(display_len)?.to_vec();
+        let ram_len = reader.u32()? as usize;
+        let ram = reader.bytes(ram_len)?.to_vec();
+        let rng = if version >= 2 {
+            let present = reader.u8()? != 0;
+            let state = reader.u64()?;
+            present.then_some(state)
+        } else {
+            None
+        };
+        let font_base = if version >= 3 { reader.u16()? } else { 0 };
+        let cycles = if version >= 4 { reader.u64()? } else { 0 };
+
+        if ram.len() != mode.ram_size() {
+            return Err(invalid("RAM size does not match the mode"));
+        }
+        let pixels = if hires {
+            HIRES_DISPLAY_WIDTH * HIRES_DISPLAY_HEIGHT
+        } else {
+            DISPLAY_WIDTH * DISPLAY_HEIGHT
+        };
+        if display.len() != pixels {
+            return Err(invalid("display size does not match the resolution"));
+        }
+        if font_base > MAX_FONT_BASE {
+            return Err(invalid("font base out of range"));
+        }
+
+        self.mode = mode;
+        self.hires = hires;
+
+        self.quirks = quirks;
+        self.exited = flags & 0b010 != 0;
+        self.vblank_wait = flags & 0b100 != 0;
+        self.key_wait = (flags & 0b1000 != 0).then_some(flags >> 4);
+        self.planes = planes;
+        self.error_policy = error_policy;
+        self.program_counter = program_counter;
+        self.ireg = ireg;
+        self.stack_pointer = stack_pointer;
+        self.instruction_pc = instruction_pc;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.pitch = pitch;
+        self.vregs = vregs;
+        self.stack = stack;
+        for (idx, key) in self.keyboard.iter_mut().enumerate() {
+            *key = keys & (1 << idx) != 0;
+        }
+        self.rpl = rpl;
+        self.audio_pattern = audio_pattern;
+        self.display = display;
+        self.ram = ram;
+        self.display_flag = true;
+        self.font_base = font_base;
+        self.cycles = cycles;
+        if let Some(state) = rng {
+            self.rng.restore(state);
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(reason: &str) -> Chip8Error {
+    Chip8Error::InvalidSaveState {
+        reason: reason.to_string(),
+    }
+}
+
+pub(crate) fn quirks_to_bits(quirks: &Quirks) -> u8 {
+    [
+        quirks.shift_uses_vy,
+        quirks.jump_uses_vx,
+        quirks.load_store_inc_i,
+        quirks.logic_resets_vf,
+        quirks.clip_sprites,
+        quirks.display_wait,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |bits, (idx, &on)| bits | (on as u8) << idx)
+}
+
+pub(crate) fn quirks_from_bits(bits: u8) -> Quirks {
+    let on = |idx: u8| bits & (1 << idx) != 0;
+    Quirks {
+        shift_uses_vy: on(0),
+        jump_uses_vx: on(1),
+        load_store_inc_i: on(2),
+        logic_resets_vf: on(3),
+        clip_sprites: on(4),
+        display_wait: on(5),
+    }
+}
+
+// reads the big endian values of save states and movies, `invalid` builds
+// the error for data that runs out early
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    invalid: fn(&str) -> Chip8Error,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8], invalid: fn(&str) -> Chip8Error) -> Self {
+        Reader {
+            data,
+            pos: 0,
+            invalid,
+        }
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| (self.invalid)("truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, Chip8Error> {
+        Ok(u16::from_be_bytes([self.u8()?, self.u8()?]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Chip8Error> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Chip8Error> {
+        Ok((self.u32()? as u64) << 32 | self.u32()? as u64)
+    }
+}
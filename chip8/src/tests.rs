@@ -1,18 +1,1528 @@
-#[cfg(test)]
-pub mod tests {
-    use crate::{
-        chip8::Chip8,
-        constants::{FONTSET_SIZE, FONT_SET},
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+
+use crate::{
+    asm::assemble,
+    chip8::Chip8,
+    constants::{BIG_FONT_SET, DEFAULT_FONT_ADDR, FONT_SET},
+    debugger::{Condition, Debugger, Operand, StopReason, Watch},
+    decode::Instruction,
+    disasm::{disassemble, Line, Syntax},
+    dump::{display_ascii, display_pbm, state_json},
+    error::{Chip8Error, ErrorPolicy},
+    mode::Mode,
+    movie::{Movie, MoviePlayer, MovieRecorder},
+    quirks::Quirks,
+    rewind::Rewind,
+    rng::ScriptedRng,
+    scheduler::{Scheduler, MAX_CATCH_UP_FRAMES},
+    trace::{BinaryTracer, TextTracer, TraceEntry, TraceFilter, Tracer},
+};
+
+#[test]
+fn font_load() {
+    let mut chip8 = Chip8::new(Quirks::default());
+
+    chip8.load_fonts();
+
+    let base = DEFAULT_FONT_ADDR as usize;
+    assert_eq!(chip8.ram[base..base + 80], FONT_SET);
+    assert_eq!(chip8.ram[base + 80..base + 240], BIG_FONT_SET);
+}
+
+#[test]
+fn font_base_is_configurable() {
+    let mut chip8 = Chip8::new(Quirks::default()).with_font_base(0x000);
+    chip8.load_fonts();
+    chip8.vregs[0] = 0xF;
+
+    chip8.execute(0xF029).unwrap();
+    assert_eq!(chip8.ireg, 75);
+    assert_eq!(chip8.ram[75..80], FONT_SET[75..80]);
+
+    chip8.execute(0xF030).unwrap();
+    assert_eq!(chip8.ireg, 80 + 150);
+}
+
+#[test]
+fn font_base_must_fit_below_the_program() {
+    assert!(Chip8::new(Quirks::default())
+        .try_with_font_base(0x110)
+        .is_ok());
+    assert!(matches!(
+        Chip8::new(Quirks::default()).try_with_font_base(0x111),
+        Err(Chip8Error::InvalidFontBase { addr: 0x111 })
+    ));
+}
+
+#[test]
+fn custom_font() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_fonts();
+    let font: Vec<u8> = (0..80).collect();
+    chip8.load_custom_font(&font).unwrap();
+    chip8.vregs[0] = 0xA;
+
+    chip8.execute(0xF029).unwrap();
+    let i = chip8.ireg as usize;
+    assert_eq!(chip8.ram[i..i + 5], [50, 51, 52, 53, 54]);
+    // the big font is untouched without one in the file
+    assert_eq!(chip8.ram[i + 30], BIG_FONT_SET[0]);
+
+    assert_eq!(
+        chip8.load_custom_font(&[0; 100]),
+        Err(Chip8Error::InvalidFont { size: 100 })
+    );
+}
+
+#[test]
+fn quirk_shift_uses_vy() {
+    let mut chip8 = Chip8::new(Quirks::cosmac_vip());
+    chip8.vregs[1] = 0x00;
+    chip8.vregs[2] = 0x04;
+
+    chip8.execute(0x8126).unwrap();
+
+    assert_eq!(chip8.vregs[1], 0x02);
+}
+
+#[test]
+fn quirk_jump_uses_vx() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+    chip8.vregs[0] = 0x10;
+    chip8.vregs[3] = 0x04;
+
+    chip8.execute(0xB300).unwrap();
+
+    assert_eq!(chip8.program_counter, 0x304);
+}
+
+#[test]
+fn quirk_load_store_inc_i() {
+    let mut chip8 = Chip8::new(Quirks::cosmac_vip());
+    chip8.ireg = 0x300;
+
+    chip8.execute(0xF355).unwrap();
+
+    assert_eq!(chip8.ireg, 0x304);
+}
+
+#[test]
+fn quirk_logic_resets_vf() {
+    let mut chip8 = Chip8::new(Quirks::cosmac_vip());
+    chip8.vregs[0xF] = 1;
+
+    chip8.execute(0x8121).unwrap();
+
+    assert_eq!(chip8.vregs[0xF], 0);
+}
+
+#[test]
+fn quirk_clip_sprites() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+    chip8.ireg = 0x300;
+    chip8.ram[0x300] = 0xFF;
+    chip8.vregs[0] = 60;
+
+    chip8.execute(0xD011).unwrap();
+
+    assert_eq!(chip8.display[63], 1);
+    assert_eq!(chip8.display[0], 0);
+}
+
+#[test]
+fn quirk_display_wait() {
+    let mut chip8 = Chip8::new(Quirks::cosmac_vip());
+    chip8.ram[0x200..0x204].copy_from_slice(&[0xD0, 0x01, 0x60, 0x42]);
+
+    chip8.tick().unwrap();
+    chip8.tick().unwrap();
+    assert_eq!(chip8.vregs[0], 0);
+
+    chip8.tick_timers();
+    chip8.tick().unwrap();
+    assert_eq!(chip8.vregs[0], 0x42);
+}
+
+#[test]
+fn schip_hires_switch() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+
+    chip8.execute(0x00FF).unwrap();
+    let (display, width, height) = chip8.get_display();
+    assert_eq!((display.len(), width, height), (128 * 64, 128, 64));
+
+    chip8.execute(0x00FE).unwrap();
+    let (display, width, height) = chip8.get_display();
+    assert_eq!((display.len(), width, height), (64 * 32, 64, 32));
+}
+
+#[test]
+fn schip_scroll() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+    chip8.display[0] = 1;
+
+    chip8.execute(0x00C2).unwrap();
+    assert_eq!(chip8.display[2 * 64], 1);
+
+    chip8.execute(0x00FB).unwrap();
+    assert_eq!(chip8.display[2 * 64 + 4], 1);
+
+    chip8.execute(0x00FC).unwrap();
+    chip8.execute(0x00FC).unwrap();
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+}
+
+#[test]
+fn schip_big_sprite() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+    chip8.set_hires(true);
+    chip8.ireg = 0x300;
+    for i in 0..32 {
+        chip8.ram[0x300 + i] = 0xFF;
+    }
+
+    chip8.execute(0xD010).unwrap();
+
+    assert_eq!(chip8.display[15 + 15 * 128], 1);
+    assert_eq!(chip8.display[16], 0);
+}
+
+#[test]
+fn schip_big_font() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+    chip8.load_fonts();
+    chip8.vregs[0] = 2;
+
+    chip8.execute(0xF030).unwrap();
+
+    let big_font = chip8.big_font_addr() as usize;
+    assert_eq!(chip8.ireg as usize, big_font + 20);
+    assert_eq!(
+        chip8.ram[big_font + 20..big_font + 30],
+        BIG_FONT_SET[20..30]
+    );
+}
+
+#[test]
+fn schip_rpl_flags() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+    chip8.vregs[0..3].copy_from_slice(&[1, 2, 3]);
+
+    chip8.execute(0xF275).unwrap();
+    chip8.vregs[0..3].copy_from_slice(&[0, 0, 0]);
+    chip8.execute(0xF185).unwrap();
+
+    assert_eq!(chip8.vregs[0..3], [1, 2, 0]);
+}
+
+#[test]
+fn schip_exit() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+    chip8.load_rom(&[0x00, 0xFD, 0x60, 0x01]).unwrap();
+
+    chip8.tick().unwrap();
+    chip8.tick().unwrap();
+
+    assert!(chip8.exited);
+    assert_eq!(chip8.vregs[0], 0);
+}
+
+#[test]
+fn xochip_ram_size() {
+    let chip8 = Chip8::new(Quirks::schip()).with_mode(Mode::XoChip);
+
+    assert_eq!(chip8.ram.len(), 0x10000);
+}
+
+#[test]
+fn xochip_long_load_and_skip() {
+    let mut chip8 = Chip8::new(Quirks::schip()).with_mode(Mode::XoChip);
+    chip8
+        .load_rom(&[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0xF0, 0x00, 0xAB, 0xCD])
+        .unwrap();
+
+    // 3000 skips the whole four byte F000 NNNN
+    chip8.tick().unwrap();
+    assert_eq!(chip8.program_counter, 0x206);
+
+    chip8.tick().unwrap();
+    assert_eq!(chip8.ireg, 0xABCD);
+    assert_eq!(chip8.program_counter, 0x20A);
+}
+
+#[test]
+fn xochip_register_range() {
+    let mut chip8 = Chip8::new(Quirks::schip()).with_mode(Mode::XoChip);
+    chip8.ireg = 0x400;
+    chip8.vregs[2..5].copy_from_slice(&[7, 8, 9]);
+
+    chip8.execute(0x5242).unwrap();
+    assert_eq!(chip8.ram[0x400..0x403], [7, 8, 9]);
+
+    // reversed ranges load backwards
+    chip8.execute(0x5423).unwrap();
+    assert_eq!(chip8.vregs[2..5], [9, 8, 7]);
+    assert_eq!(chip8.ireg, 0x400);
+}
+
+#[test]
+fn xochip_planes() {
+    let mut chip8 = Chip8::new(Quirks::schip()).with_mode(Mode::XoChip);
+    chip8.ireg = 0x300;
+    chip8.ram[0x300] = 0x80;
+    chip8.ram[0x301] = 0x80;
+
+    chip8.execute(0xF301).unwrap();
+    chip8.execute(0xD001).unwrap();
+    assert_eq!(chip8.display[0], 0b11);
+
+    chip8.execute(0xF201).unwrap();
+    chip8.execute(0x00E0).unwrap();
+    assert_eq!(chip8.display[0], 0b01);
+}
+
+#[test]
+fn xochip_audio() {
+    let mut chip8 = Chip8::new(Quirks::schip()).with_mode(Mode::XoChip);
+    chip8.ireg = 0x300;
+    chip8.ram[0x300..0x310].copy_from_slice(&[0xAA; 16]);
+    chip8.vregs[1] = 100;
+
+    chip8.execute(0xF002).unwrap();
+    chip8.execute(0xF13A).unwrap();
+
+    assert_eq!(chip8.audio_pattern, [0xAA; 16]);
+    assert_eq!(chip8.pitch, 100);
+}
+
+#[test]
+fn xochip_opcodes_need_xochip_mode() {
+    let mut chip8 = Chip8::new(Quirks::schip());
+    chip8.load_rom(&[0xF0, 0x00, 0x12, 0x34]).unwrap();
+
+    assert_eq!(
+        chip8.tick(),
+        Err(Chip8Error::UnknownOpcode {
+            opcode: 0xF000,
+            pc: 0x200
+        })
+    );
+    assert_eq!(chip8.ireg, 0);
+}
+
+#[test]
+fn error_rom_too_large() {
+    let mut chip8 = Chip8::new(Quirks::default());
+
+    assert_eq!(
+        chip8.load_rom(&[0; 4096]),
+        Err(Chip8Error::RomTooLarge {
+            size: 4096,
+            max: 3584
+        })
+    );
+    assert!(chip8.load_rom(&[0; 3584]).is_ok());
+}
+
+#[test]
+fn error_stack() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&[0x00, 0xEE]).unwrap();
+
+    assert_eq!(chip8.tick(), Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+
+    // a subroutine that keeps calling itself
+    chip8.load_rom(&[0x22, 0x00]).unwrap();
+    for _ in 0..16 {
+        chip8.tick().unwrap();
+    }
+    assert_eq!(chip8.tick(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+}
+
+#[test]
+fn error_memory_out_of_bounds() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&[0xAF, 0xFE, 0xF2, 0x55]).unwrap();
+
+    chip8.tick().unwrap();
+
+    assert_eq!(
+        chip8.tick(),
+        Err(Chip8Error::MemoryOutOfBounds {
+            addr: 0x1000,
+            pc: 0x202
+        })
+    );
+}
+
+#[test]
+fn error_invalid_key() {
+    let mut chip8 = Chip8::new(Quirks::default());
+
+    assert_eq!(
+        chip8.keypress(16, true),
+        Err(Chip8Error::InvalidKey { key: 16 })
+    );
+}
+
+#[test]
+fn error_policy_halt() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&[0xFF, 0xFF]).unwrap();
+
+    assert!(chip8.tick().is_err());
+    assert!(chip8.tick().is_err());
+    assert_eq!(chip8.program_counter, 0x200);
+}
+
+#[test]
+fn error_policy_ignore() {
+    let mut chip8 = Chip8::new(Quirks::default()).with_error_policy(ErrorPolicy::Ignore);
+    chip8.load_rom(&[0xFF, 0xFF, 0x60, 0x01]).unwrap();
+
+    chip8.tick().unwrap();
+    chip8.tick().unwrap();
+
+    assert_eq!(chip8.vregs[0], 1);
+}
+
+#[test]
+fn machine_code_calls_ignored() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8
+        .load_rom(&[0x01, 0x23, 0x00, 0x00, 0x60, 0x01])
+        .unwrap();
+
+    for _ in 0..3 {
+        chip8.tick().unwrap();
+    }
+    assert_eq!(chip8.vregs[0], 1);
+
+    // XO-CHIP has no machine code to call
+    let mut chip8 = Chip8::new(Quirks::default()).with_mode(Mode::XoChip);
+    chip8.load_rom(&[0x01, 0x23]).unwrap();
+    assert!(chip8.tick().is_err());
+}
+
+#[test]
+fn skip_wraps_at_end_of_memory() {
+    let mut chip8 = Chip8::new(Quirks::default()).with_mode(Mode::XoChip);
+    chip8.load_rom(&[]).unwrap();
+    chip8.ram[0xFFFC..].copy_from_slice(&[0x30, 0x00, 0xF0, 0x00]);
+    chip8.program_counter = 0xFFFC;
+
+    chip8.tick().unwrap();
+    assert_eq!(chip8.program_counter, 0x0002);
+}
+
+#[test]
+fn wait_key_at_end_of_memory() {
+    let mut chip8 = Chip8::new(Quirks::default()).with_mode(Mode::XoChip);
+    chip8.load_rom(&[]).unwrap();
+    chip8.ram[0xFFFE..].copy_from_slice(&[0xF0, 0x0A]);
+    chip8.program_counter = 0xFFFE;
+
+    chip8.tick().unwrap();
+    assert_eq!(chip8.program_counter, 0xFFFE);
+
+    chip8.keypress(0x3, true).unwrap();
+    chip8.tick().unwrap();
+    chip8.keypress(0x3, false).unwrap();
+    chip8.tick().unwrap();
+    assert_eq!(chip8.vregs[0], 0x3);
+    assert_eq!(chip8.program_counter, 0x0000);
+}
+
+#[test]
+fn wait_key_takes_the_key_on_release() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&[0xF5, 0x0A, 0x60, 0x01]).unwrap();
+
+    chip8.keypress(0xB, true).unwrap();
+    chip8.tick().unwrap();
+    chip8.keypress(0x2, true).unwrap();
+    chip8.tick().unwrap();
+    assert_eq!(chip8.program_counter, 0x200);
+    assert_eq!(chip8.key_wait, Some(0xB));
+
+    // the wait survives a save state
+    let mut restored = Chip8::new(Quirks::default());
+    restored.load_state(&chip8.save_state()).unwrap();
+    assert_eq!(restored.key_wait, Some(0xB));
+
+    restored.keypress(0xB, false).unwrap();
+    restored.tick().unwrap();
+    assert_eq!(restored.vregs[5], 0xB);
+    assert_eq!(restored.key_wait, None);
+    assert_eq!(restored.program_counter, 0x202);
+}
+
+#[test]
+fn decode_instructions() {
+    assert_eq!(
+        Instruction::decode(0xD125),
+        Some(Instruction::Draw { x: 1, y: 2, n: 5 })
+    );
+    assert_eq!(Instruction::decode(0x2ABC), Some(Instruction::Call(0xABC)));
+    assert_eq!(Instruction::decode(0xF000), Some(Instruction::LoadLongI));
+    assert_eq!(Instruction::decode(0x0123), None);
+    assert_eq!(Instruction::decode(0xE1FF), None);
+}
+
+#[test]
+fn display_instructions() {
+    let text = |opcode| Instruction::decode(opcode).unwrap().to_string();
+
+    assert_eq!(text(0x00E0), "CLS");
+    assert_eq!(text(0x1228), "JP 0x228");
+    assert_eq!(text(0x6A0F), "LD VA, 0x0F");
+    assert_eq!(text(0x8AB4), "ADD VA, VB");
+    assert_eq!(text(0xD015), "DRW V0, V1, 5");
+    assert_eq!(text(0xF265), "LD V2, [I]");
+}
+
+#[test]
+fn disassemble_separates_code_and_data() {
+    // jump over a sprite byte, then draw it and loop forever
+    let rom = [0x12, 0x03, 0xF0, 0xA2, 0x02, 0xD0, 0x01, 0x12, 0x07];
+    let lines = disassemble(&rom, 0x200);
+
+    assert_eq!(
+        lines[1],
+        Line::Data {
+            addr: 0x202,
+            byte: 0xF0
+        }
+    );
+    assert_eq!(
+        lines[1].format(Syntax::Cowgod),
+        "0x202  F0         DB 0xF0        ; ####...."
+    );
+    assert_eq!(
+        lines[2].format(Syntax::Cowgod),
+        "0x203  A202       LD I, 0x202"
+    );
+    assert_eq!(
+        lines[3].format(Syntax::Octo),
+        "0x205  D001       sprite v0 v0 1"
+    );
+    assert_eq!(lines.len(), 5);
+}
+
+#[test]
+fn disassemble_follows_skips_and_calls() {
+    // 3000 skips the F000 NNNN long load, the subroutine sits after a halt
+    let rom = [
+        0x30, 0x00, 0xF0, 0x00, 0x12, 0x34, 0x22, 0x0C, 0x12, 0x08, 0xFF, 0xFF, 0x00, 0xEE,
+    ];
+    let lines = disassemble(&rom, 0x200);
+    let addrs: Vec<u16> = lines
+        .iter()
+        .filter(|line| matches!(line, Line::Code { .. }))
+        .map(|line| line.addr())
+        .collect();
+
+    assert_eq!(addrs, [0x200, 0x202, 0x206, 0x208, 0x20C]);
+}
+
+#[test]
+fn encode_is_inverse_of_decode() {
+    for opcode in 0..=0xFFFF {
+        if let Some(instruction) = Instruction::decode(opcode) {
+            assert_eq!(instruction.encode(), opcode, "{}", instruction);
+        }
+    }
+}
+
+#[test]
+fn assemble_program() {
+    let source = "
+        SPEED equ 2         ; a constant
+        start:  ld v0, 0
+        loop:   add V0, SPEED
+                LD I, sprite
+                drw v0, v1, 5
+                jp loop
+                ld i, long $ + 2
+        sprite: db 0xF0, %10010000, \"ok\"
+                dw start + 2
+    ";
+    let assembly = assemble(source).unwrap();
+
+    assert_eq!(
+        assembly.bytes,
+        [
+            0x60, 0x00, 0x70, 0x02, 0xA2, 0x0E, 0xD0, 0x15, 0x12, 0x02, 0xF0, 0x00, 0x02, 0x0C,
+            0xF0, 0x90, b'o', b'k', 0x02, 0x02,
+        ]
+    );
+    assert_eq!(assembly.symbols["loop"], 0x202);
+    assert_eq!(assembly.symbols["SPEED"], 2);
+    assert!(assembly.symbol_map().contains("sprite 0x020E\n"));
+}
+
+#[test]
+fn assemble_errors() {
+    let error = assemble("CLS\nJP nowhere\n").unwrap_err();
+    assert_eq!(error.line, 2);
+    assert_eq!(error.message, "undefined symbol 'nowhere'");
+
+    let error = assemble("\n\nLD V0, 0x100").unwrap_err();
+    assert_eq!(error.line, 3);
+
+    let error = assemble("a:\na:").unwrap_err();
+    assert_eq!(error.message, "'a' is defined more than once");
+
+    let error = assemble("MOV V0, V1").unwrap_err();
+    assert_eq!(error.message, "unknown mnemonic 'MOV'");
+}
+
+#[test]
+fn assembled_rom_runs() {
+    let assembly = assemble("LD V0, 5\nLD V1, 7\nADD V0, V1\nEXIT").unwrap();
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&assembly.bytes).unwrap();
+
+    for _ in 0..4 {
+        chip8.tick().unwrap();
+    }
+
+    assert!(chip8.exited);
+    assert_eq!(chip8.vregs[0], 12);
+}
+
+#[test]
+fn dump_display() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.display[1] = 1;
+
+    let ascii = display_ascii(&chip8);
+    assert_eq!(ascii.lines().count(), 32);
+    assert!(ascii.starts_with(".#....."));
+
+    let pbm = display_pbm(&chip8);
+    assert!(pbm.starts_with("P1\n64 32\n0 1 0 0"));
+}
+
+#[test]
+fn dump_state() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.vregs[0xF] = 7;
+
+    let json = state_json(&chip8);
+    assert!(json.contains("\"pc\": 512,"));
+    assert!(json.contains("\"v\": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 7],"));
+}
+
+#[test]
+fn save_state_round_trip() {
+    let assembly = assemble("HIGH\nLD V0, 5\nLD I, 0x300\nCALL 0x20A\nJP 0x208\nRET").unwrap();
+    let mut chip8 = Chip8::new(Quirks::schip()).with_mode(Mode::XoChip);
+    chip8.load_fonts();
+    chip8.load_rom(&assembly.bytes).unwrap();
+    for _ in 0..4 {
+        chip8.tick().unwrap();
+    }
+    chip8.keypress(0xA, true).unwrap();
+    chip8.display[5] = 3;
+    chip8.delay_timer = 9;
+
+    let state = chip8.save_state();
+    let mut restored = Chip8::new(Quirks::default());
+    restored.load_state(&state).unwrap();
+
+    assert_eq!(restored.save_state(), state);
+    assert_eq!(restored.mode, Mode::XoChip);
+    assert_eq!(restored.quirks, Quirks::schip());
+    assert!(restored.hires);
+    assert_eq!(restored.stack_pointer, 1);
+    assert_eq!(restored.ireg, 0x300);
+    assert!(restored.keyboard[0xA]);
+    assert_eq!(restored.display[5], 3);
+    assert_eq!(restored.cycles, 4);
+
+    restored.tick().unwrap();
+    assert_eq!(restored.program_counter, 0x208);
+}
+
+#[test]
+fn load_state_reads_old_versions() {
+    let mut chip8 = Chip8::new(Quirks::default()).with_font_base(0x050);
+    chip8.load_rom(&[0x60, 0x01]).unwrap();
+    chip8.tick().unwrap();
+
+    // version 3 is version 4 without the cycle count on the end
+    let mut state = chip8.save_state();
+    state.truncate(state.len() - 8);
+    state[4] = 3;
+    let mut restored = Chip8::new(Quirks::default());
+    restored.cycles = 99;
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.cycles, 0);
+    assert_eq!(restored.font_base, 0x050);
+    assert_eq!(restored.vregs[0], 1);
+
+    // and version 2 also without the font base
+    state.truncate(state.len() - 2);
+    state[4] = 2;
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.font_base, 0x000);
+}
+
+#[test]
+fn load_state_rejects_bad_data() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    let mut state = chip8.save_state();
+
+    assert!(matches!(
+        chip8.load_state(b"nope"),
+        Err(Chip8Error::InvalidSaveState { .. })
+    ));
+    assert!(chip8.load_state(&state[..state.len() - 1]).is_err());
+
+    // the font base sits just before the cycle count
+    let font_base = state.len() - 10;
+    state[font_base..font_base + 2].copy_from_slice(&[0xFF, 0xFF]);
+    assert_eq!(
+        chip8.load_state(&state).unwrap_err().to_string(),
+        "invalid save state: font base out of range"
+    );
+    assert_eq!(chip8.font_base, 0x050);
+
+    state[4] = 99;
+    assert_eq!(
+        chip8.load_state(&state).unwrap_err().to_string(),
+        "invalid save state: unsupported version 99"
+    );
+}
+
+#[test]
+fn rewind_steps_back_frame_by_frame() {
+    let rom = assemble("HIGH\nloop: ADD V0, 1\nLD I, 0x300\nLD [I], V0\nJP loop")
+        .unwrap()
+        .bytes;
+    let mut chip8 = Chip8::new(Quirks::schip()).with_seed(1);
+    chip8.load_rom(&rom).unwrap();
+    let mut rewind = Rewind::new(100, usize::MAX);
+    let mut states = Vec::new();
+
+    for _ in 0..10 {
+        rewind.push(&chip8);
+        states.push(chip8.save_state());
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+        }
+    }
+    rewind.push(&chip8);
+    assert_eq!(rewind.len(), 10);
+
+    while let Some(state) = states.pop() {
+        assert!(rewind.step_back(&mut chip8));
+        assert_eq!(chip8.save_state(), state);
+    }
+    assert!(!rewind.step_back(&mut chip8));
+    assert!(!chip8.hires);
+}
+
+#[test]
+fn rewind_drops_the_oldest_frames() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    let mut rewind = Rewind::new(5, usize::MAX);
+    for frame in 0..20 {
+        chip8.vregs[0] = frame;
+        rewind.push(&chip8);
+    }
+    assert_eq!(rewind.len(), 5);
+    while rewind.step_back(&mut chip8) {}
+    assert_eq!(chip8.vregs[0], 14);
+
+    let mut rewind = Rewind::new(1000, 7000);
+    for frame in 0..200 {
+        chip8.ram[0x300 + frame] = 0xFF;
+        rewind.push(&chip8);
+        assert!(rewind.memory_used() <= 7000);
+    }
+    assert!(rewind.len() < 199);
+    assert!(rewind.len() > 100, "frame deltas should be small");
+
+    rewind.clear();
+    assert!(rewind.is_empty());
+    assert_eq!(rewind.memory_used(), 0);
+}
+
+const MOVIE_PROGRAM: &str = "LD V0, 5\nloop: SKNP V0\nADD V1, 1\nRND V2, 0xFF\nADD V3, V2\nJP loop";
+
+fn record_movie(rom: &[u8]) -> (Movie, Chip8) {
+    let mut chip8 = Chip8::new(Quirks::default()).with_seed(99);
+    chip8.load_rom(rom).unwrap();
+    let mut recorder = MovieRecorder::new(&chip8, rom, 600);
+    let mut scheduler = Scheduler::new(600);
+
+    for frame in 0..150 {
+        if frame % 40 == 10 {
+            recorder.keypress(&mut chip8, 5, true).unwrap();
+            // already held, so not recorded again
+            recorder.keypress(&mut chip8, 5, true).unwrap();
+        }
+        if frame % 40 == 25 {
+            recorder.keypress(&mut chip8, 5, false).unwrap();
+        }
+        scheduler.run_frame(&mut chip8).unwrap();
+        recorder.end_frame(&chip8);
+    }
+    (recorder.finish(&chip8), chip8)
+}
+
+fn play_movie(movie: &Movie, rom: &[u8], seed: u64) -> Result<Chip8, Chip8Error> {
+    movie.check_rom(rom)?;
+    let header = &movie.header;
+    let mut chip8 = Chip8::new(header.quirks)
+        .with_mode(header.mode)
+        .with_seed(seed);
+    chip8.load_rom(rom).unwrap();
+    let mut player = MoviePlayer::new(movie.clone());
+    let mut scheduler = Scheduler::new(header.ips);
+
+    while !player.is_finished() {
+        player.begin_frame(&mut chip8)?;
+        scheduler.run_frame(&mut chip8)?;
+        player.end_frame(&chip8)?;
+    }
+    Ok(chip8)
+}
+
+#[test]
+fn movie_round_trip() {
+    let rom = assemble(MOVIE_PROGRAM).unwrap().bytes;
+    let (movie, recorded) = record_movie(&rom);
+    assert_eq!(movie.frames, 150);
+    assert_eq!(movie.header.seed, 99);
+    // 4 presses, 4 releases, hashes at 60, 120 and the end
+    assert_eq!(movie.events.len(), 11);
+    assert_eq!(Movie::from_bytes(&movie.to_bytes()).unwrap(), movie);
+
+    let played = play_movie(&movie, &rom, movie.header.seed).unwrap();
+    assert_eq!(played.save_state(), recorded.save_state());
+}
+
+#[test]
+fn movie_playback_detects_desync() {
+    let rom = assemble(MOVIE_PROGRAM).unwrap().bytes;
+    let (movie, _) = record_movie(&rom);
+
+    assert!(matches!(
+        play_movie(&movie, &rom, 1),
+        Err(Chip8Error::MovieDesync { frame: 60 })
+    ));
+    assert_eq!(
+        play_movie(&movie, &rom[2..], 99).err().unwrap().to_string(),
+        "invalid movie: recorded with a different ROM"
+    );
+
+    let mut bytes = movie.to_bytes();
+    bytes.truncate(bytes.len() - 3);
+    assert!(matches!(
+        Movie::from_bytes(&bytes),
+        Err(Chip8Error::InvalidMovie { .. })
+    ));
+}
+
+#[test]
+fn movie_rejects_font_base_over_program() {
+    let rom = assemble(MOVIE_PROGRAM).unwrap().bytes;
+    let (mut movie, _) = record_movie(&rom);
+    movie.header.font_base = 0xFFFF;
+
+    assert_eq!(
+        Movie::from_bytes(&movie.to_bytes())
+            .unwrap_err()
+            .to_string(),
+        "invalid movie: font base out of range"
+    );
+}
+
+#[test]
+fn random_is_reproducible_with_a_seed() {
+    let rom = assemble("RND V0, 0xFF\nRND V1, 0xFF\nRND V2, 0xFF")
+        .unwrap()
+        .bytes;
+    let run = |seed| {
+        let mut chip8 = Chip8::new(Quirks::default()).with_seed(seed);
+        chip8.load_rom(&rom).unwrap();
+        for _ in 0..3 {
+            chip8.tick().unwrap();
+        }
+        chip8.vregs
     };
 
-    use super::*;
+    assert_eq!(run(42), run(42));
+    assert_ne!(run(42), run(43));
+}
+
+#[test]
+fn random_from_scripted_source() {
+    let mut chip8 = Chip8::new(Quirks::default()).with_rng(ScriptedRng::new(&[0xAB, 0xFF]));
+
+    chip8.execute(0xC0FF).unwrap();
+    chip8.execute(0xC10F).unwrap();
+    chip8.execute(0xC2F0).unwrap();
+
+    assert_eq!(chip8.vregs[0..3], [0xAB, 0x0F, 0xA0]);
+}
+
+#[test]
+fn save_state_keeps_rng_position() {
+    let mut chip8 = Chip8::new(Quirks::default()).with_seed(7);
+    chip8.execute(0xC0FF).unwrap();
+    let state = chip8.save_state();
+    chip8.execute(0xC0FF).unwrap();
+    let expected = chip8.vregs[0];
+
+    let mut restored = Chip8::new(Quirks::default());
+    restored.load_state(&state).unwrap();
+    restored.execute(0xC0FF).unwrap();
+
+    assert_eq!(restored.vregs[0], expected);
+}
 
-    #[test]
-    fn font_load() {
-        let mut chip8 = Chip8::new();
+#[test]
+fn sound_active_while_timer_runs() {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.vregs[0] = 2;
+    chip8.execute(0xF018).unwrap();
+
+    assert!(chip8.is_sound_active());
+    chip8.tick_timers();
+    assert!(chip8.is_sound_active());
+    chip8.tick_timers();
+    assert!(!chip8.is_sound_active());
+}
+
+#[test]
+fn scheduler_runs_frames_at_60hz() {
+    let mut scheduler = Scheduler::default();
+    let frame = Scheduler::frame_time();
+
+    // a 144 Hz display calls in more often than frames are due
+    let refresh = Duration::from_nanos(1_000_000_000 / 144);
+    let due: u32 = (0..144).map(|_| scheduler.advance(refresh)).sum();
+    assert!((59..=60).contains(&due));
+
+    let mut scheduler = Scheduler::default();
+    assert_eq!(scheduler.advance(frame / 2), 0);
+    assert_eq!(scheduler.until_next_frame(), frame - frame / 2);
+
+    // a long stall only catches up a few frames
+    assert_eq!(
+        scheduler.advance(Duration::from_secs(3)),
+        MAX_CATCH_UP_FRAMES
+    );
+    assert!(scheduler.advance(Duration::ZERO) <= 1);
+}
+
+#[test]
+fn scheduler_speed() {
+    let frame = Scheduler::frame_time();
+
+    let mut scheduler = Scheduler::default();
+    scheduler.set_speed(0.25);
+    let due: Vec<u32> = (0..8).map(|_| scheduler.advance(frame)).collect();
+    assert_eq!(due, [0, 0, 0, 1, 0, 0, 0, 1]);
+    // give or take rounding in the scaling
+    let wait = scheduler.until_next_frame();
+    assert!(
+        wait.abs_diff(frame * 4) < Duration::from_micros(1),
+        "{:?}",
+        wait
+    );
+
+    let mut scheduler = Scheduler::default();
+    scheduler.set_speed(4.0);
+    assert_eq!(scheduler.advance(frame), 4);
+    assert_eq!(
+        scheduler.advance(Duration::from_secs(3)),
+        4 * MAX_CATCH_UP_FRAMES
+    );
+}
 
+#[test]
+fn scheduler_spreads_instructions_over_a_second() {
+    let mut scheduler = Scheduler::new(700);
+    let cycles: Vec<u64> = (0..60).map(|_| scheduler.next_frame_cycles()).collect();
+
+    assert_eq!(cycles.iter().sum::<u64>(), 700);
+    assert!(cycles.iter().all(|&n| n == 11 || n == 12));
+}
+
+#[test]
+fn scheduler_run_frame() {
+    // 1200: JP 0x200, an endless loop
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8.load_rom(&[0x12, 0x00]).unwrap();
+    chip8.delay_timer = 5;
+    let mut scheduler = Scheduler::new(600);
+
+    scheduler.run_frame(&mut chip8).unwrap();
+
+    assert_eq!(chip8.delay_timer, 4);
+}
+
+const DEBUG_PROGRAM: &str = "
+        LD V0, 1
+        CALL sub
+        ADD V0, 1
+        LD V3, 0x10
+loop:   JP loop
+sub:    LD V1, 2
+        RET
+";
+
+fn debug_machine() -> (Debugger, Chip8) {
+    let mut chip8 = Chip8::new(Quirks::default());
+    chip8
+        .load_rom(&assemble(DEBUG_PROGRAM).unwrap().bytes)
+        .unwrap();
+    (Debugger::new(), chip8)
+}
+
+fn run_until_stop(debugger: &mut Debugger, chip8: &mut Chip8) -> StopReason {
+    (0..100)
+        .find_map(|_| debugger.tick(chip8))
+        .expect("debugger never stopped")
+}
+
+#[test]
+fn debugger_breakpoint_and_resume() {
+    let (mut debugger, mut chip8) = debug_machine();
+    debugger.add_breakpoint(0x204, None);
+    debugger.add_breakpoint(0x206, None);
+
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut chip8),
+        StopReason::Breakpoint { addr: 0x204 }
+    );
+    assert!(debugger.is_paused());
+    assert_eq!(debugger.tick(&mut chip8), None);
+    assert_eq!(chip8.program_counter, 0x204);
+
+    debugger.resume();
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut chip8),
+        StopReason::Breakpoint { addr: 0x206 }
+    );
+    assert_eq!(chip8.vregs[0], 2);
+}
+
+#[test]
+fn debugger_resume_from_breakpoint_after_draw() {
+    // display wait holds the instruction after DXYN until vblank
+    let mut chip8 = Chip8::new(Quirks::cosmac_vip());
+    chip8
+        .load_rom(
+            &assemble("loop: DRW V0, V0, 1\nADD V0, 1\nJP loop")
+                .unwrap()
+                .bytes,
+        )
+        .unwrap();
+    let mut debugger = Debugger::new();
+    debugger.add_breakpoint(0x202, None);
+
+    assert_eq!(
+        debugger.run_frame(&mut chip8, 10),
+        Some(StopReason::Breakpoint { addr: 0x202 })
+    );
+    assert!(chip8.vblank_wait);
+
+    // the rest of the frame waits, the next one gets past the breakpoint
+    // and comes round to it again
+    debugger.resume();
+    assert_eq!(debugger.run_frame(&mut chip8, 10), None);
+    assert_eq!(chip8.vregs[0], 0);
+    assert_eq!(
+        debugger.run_frame(&mut chip8, 10),
+        Some(StopReason::Breakpoint { addr: 0x202 })
+    );
+    assert_eq!(chip8.vregs[0], 1);
+}
+
+#[test]
+fn debugger_conditional_breakpoint() {
+    let (mut debugger, mut chip8) = debug_machine();
+    debugger.add_breakpoint(0x200, Some(Condition::parse("V0 == 5").unwrap()));
+    debugger.add_breakpoint(0x208, Some(Condition::parse("V3 == 0x10").unwrap()));
+
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut chip8),
+        StopReason::Breakpoint { addr: 0x208 }
+    );
+    assert_eq!(chip8.vregs[3], 0x10);
+}
+
+#[test]
+fn debugger_stepping() {
+    let (mut debugger, mut chip8) = debug_machine();
+    debugger.pause();
+
+    debugger.step();
+    assert_eq!(run_until_stop(&mut debugger, &mut chip8), StopReason::Step);
+    assert_eq!(chip8.program_counter, 0x202);
+
+    debugger.step_over(&chip8);
+    assert_eq!(run_until_stop(&mut debugger, &mut chip8), StopReason::Step);
+    assert_eq!(chip8.program_counter, 0x204);
+    assert_eq!(chip8.vregs[1], 2);
+
+    let (mut debugger, mut chip8) = debug_machine();
+    debugger.add_breakpoint(0x20A, None);
+    run_until_stop(&mut debugger, &mut chip8);
+    debugger.step_out(&chip8);
+    assert_eq!(run_until_stop(&mut debugger, &mut chip8), StopReason::Step);
+    assert_eq!(chip8.program_counter, 0x204);
+}
+
+#[test]
+fn debugger_watchpoints() {
+    let (mut debugger, mut chip8) = debug_machine();
+    debugger.add_watch(Watch::parse("V3").unwrap());
+
+    assert_eq!(
+        run_until_stop(&mut debugger, &mut chip8),
+        StopReason::Watchpoint {
+            watch: Watch::Vreg(3),
+            old: 0,
+            new: 0x10
+        }
+    );
+
+    let mut chip8 = Chip8::new(Quirks::default());
+    // 6005 A300 F055: LD V0, 5; LD I, 0x300; LD [I], V0
+    chip8
+        .load_rom(&[0x60, 0x05, 0xA3, 0x00, 0xF0, 0x55])
+        .unwrap();
+    let mut debugger = Debugger::new();
+    debugger.add_watch(Watch::parse("0x300").unwrap());
+    assert!(matches!(
+        run_until_stop(&mut debugger, &mut chip8),
+        StopReason::Watchpoint { new: 5, .. }
+    ));
+    assert_eq!(chip8.program_counter, 0x206);
+}
+
+#[test]
+fn debugger_parse_conditions() {
+    let condition = Condition::parse("[0x300]<=V3").unwrap();
+    assert_eq!(condition.left, Operand::Ram(0x300));
+    assert_eq!(condition.comparison, "<=");
+    assert_eq!(condition.right, Operand::Vreg(3));
+    assert_eq!(condition.to_string(), "[0x300] <= V3");
+
+    assert!(Condition::parse("V3 = 1").is_err());
+    assert!(Condition::parse("VG == 1").is_err());
+}
+
+struct Recorder(Rc<RefCell<Vec<TraceEntry>>>);
+
+impl Tracer for Recorder {
+    fn trace(&mut self, entry: &TraceEntry) {
+        self.0.borrow_mut().push(entry.clone());
+    }
+}
+
+#[test]
+fn trace_records_each_instruction() {
+    let entries = Rc::new(RefCell::new(Vec::new()));
+    let mut chip8 = Chip8::new(Quirks::default()).with_tracer(Recorder(entries.clone()));
+    chip8
+        .load_rom(&assemble(DEBUG_PROGRAM).unwrap().bytes)
+        .unwrap();
+    for _ in 0..5 {
+        chip8.tick().unwrap();
+    }
+
+    let entries = entries.borrow();
+    let pcs: Vec<u16> = entries.iter().map(|entry| entry.pc).collect();
+    assert_eq!(pcs, [0x200, 0x202, 0x20A, 0x20C, 0x204]);
+    assert_eq!(entries[2].cycle, 2);
+    assert_eq!(entries[2].opcode, 0x6102);
+    assert_eq!(entries[2].vregs[1], 2);
+    assert_eq!(chip8.cycles, 5);
+    assert_eq!(
+        entries[0].to_text(),
+        "       0 0200 6001 LD V0, 0x01        01 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 I=0000"
+    );
+}
+
+#[test]
+fn trace_shows_long_load_address() {
+    let entries = Rc::new(RefCell::new(Vec::new()));
+    let mut chip8 = Chip8::new(Quirks::default())
+        .with_mode(Mode::XoChip)
+        .with_tracer(Recorder(entries.clone()));
+    chip8.load_rom(&[0xF0, 0x00, 0x12, 0x34]).unwrap();
+    chip8.tick().unwrap();
+
+    let entries = entries.borrow();
+    assert_eq!(entries[0].long, Some(0x1234));
+    assert!(entries[0]
+        .to_text()
+        .starts_with("       0 0200 F000 LD I, LONG 0x1234  00"));
+}
+
+#[test]
+fn trace_writers_and_filters() {
+    let entry = |cycle, pc| TraceEntry {
+        cycle,
+        pc,
+        opcode: 0xA123,
+        vregs: [0; 16],
+        ireg: 0x123,
+        long: None,
+    };
+    let filter = TraceFilter {
+        addrs: Some(0x200..=0x2FF),
+        cycles: Some(10..20),
+    };
+
+    let mut text = Vec::new();
+    let mut tracer = TextTracer::new(&mut text, filter.clone());
+    tracer.trace(&entry(5, 0x200));
+    tracer.trace(&entry(10, 0x300));
+    tracer.trace(&entry(12, 0x2FE));
+    tracer.finish().unwrap();
+    let text = String::from_utf8(text).unwrap();
+    assert_eq!(text.lines().count(), 1);
+    assert!(text.starts_with("      12 02FE A123 LD I, 0x123"));
+
+    let mut binary = Vec::new();
+    let mut tracer = BinaryTracer::new(&mut binary, filter);
+    tracer.trace(&entry(12, 0x2FE));
+    tracer.finish().unwrap();
+    assert_eq!(binary.len(), 5 + 30);
+    assert_eq!(binary[..5], *b"C8TR\x01");
+    assert_eq!(binary[5 + 8..5 + 12], [0x02, 0xFE, 0xA1, 0x23]);
+}
+
+/// Machine state for an opcode test, applied before the program runs from
+/// 0x200, by default for one tick per opcode.
+#[derive(Default)]
+struct Setup {
+    quirks: Quirks,
+    vregs: Vec<(usize, u8)>,
+    ireg: u16,
+    ram: Vec<(usize, Vec<u8>)>,
+    keys: Vec<usize>,
+}
+
+impl Setup {
+    fn v(mut self, x: usize, value: u8) -> Self {
+        self.vregs.push((x, value));
+        self
+    }
+
+    fn i(mut self, addr: u16) -> Self {
+        self.ireg = addr;
+        self
+    }
+
+    fn ram(mut self, addr: usize, bytes: &[u8]) -> Self {
+        self.ram.push((addr, bytes.to_vec()));
+        self
+    }
+
+    fn key(mut self, key: usize) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    fn run(self, program: &[u16]) -> Chip8 {
+        let ticks = program.len();
+        self.run_ticks(program, ticks)
+    }
+
+    fn run_ticks(self, program: &[u16], ticks: usize) -> Chip8 {
+        let mut chip8 = Chip8::new(self.quirks);
         chip8.load_fonts();
+        let rom: Vec<u8> = program.iter().flat_map(|op| op.to_be_bytes()).collect();
+        chip8.load_rom(&rom).unwrap();
+        for (x, value) in self.vregs {
+            chip8.vregs[x] = value;
+        }
+        chip8.ireg = self.ireg;
+        for (addr, bytes) in self.ram {
+            chip8.ram[addr..addr + bytes.len()].copy_from_slice(&bytes);
+        }
+        for key in self.keys {
+            chip8.keypress(key, true).unwrap();
+        }
+        for _ in 0..ticks {
+            chip8.tick().unwrap();
+        }
+        chip8
+    }
+}
+
+fn setup() -> Setup {
+    Setup::default()
+}
+
+#[test]
+fn op_00e0_clears_the_display() {
+    let chip8 = setup().i(0x300).ram(0x300, &[0xFF]).run(&[0xD001, 0x00E0]);
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+}
+
+#[test]
+fn op_1nnn_jumps() {
+    let chip8 = setup().run(&[0x1ABC]);
+    assert_eq!(chip8.program_counter, 0xABC);
+}
+
+#[test]
+fn op_2nnn_and_00ee_use_the_stack() {
+    // 0x200 CALL 0x204, 0x202 never runs, 0x204 CALL 0x208 or RET
+    let chip8 = setup().run_ticks(&[0x2204, 0x0000, 0x2208], 2);
+    assert_eq!(chip8.stack_pointer, 2);
+    assert_eq!(chip8.stack[..2], [0x202, 0x206]);
+    assert_eq!(chip8.program_counter, 0x208);
+
+    let chip8 = setup().run_ticks(&[0x2204, 0x0000, 0x00EE], 2);
+    assert_eq!(chip8.stack_pointer, 0);
+    assert_eq!(chip8.program_counter, 0x202);
+}
+
+#[test]
+fn op_skips() {
+    let pc_after = |setup: Setup, opcode| setup.run(&[opcode]).program_counter;
+
+    assert_eq!(pc_after(setup().v(1, 0x42), 0x3142), 0x204);
+    assert_eq!(pc_after(setup().v(1, 0x41), 0x3142), 0x202);
+    assert_eq!(pc_after(setup().v(1, 0x41), 0x4142), 0x204);
+    assert_eq!(pc_after(setup().v(1, 0x42), 0x4142), 0x202);
+    assert_eq!(pc_after(setup().v(1, 7).v(2, 7), 0x5120), 0x204);
+    assert_eq!(pc_after(setup().v(1, 7).v(2, 8), 0x5120), 0x202);
+    assert_eq!(pc_after(setup().v(1, 7).v(2, 8), 0x9120), 0x204);
+    assert_eq!(pc_after(setup().v(1, 7).v(2, 7), 0x9120), 0x202);
+}
+
+#[test]
+fn op_6xnn_and_7xnn() {
+    let chip8 = setup().run(&[0x6AFE, 0x7A03]);
+    // 7XNN wraps without touching the carry flag
+    assert_eq!(chip8.vregs[0xA], 0x01);
+    assert_eq!(chip8.vregs[0xF], 0);
+}
+
+#[test]
+fn op_8xy0_to_8xy3() {
+    let regs = || setup().v(1, 0b1100).v(2, 0b1010).v(0xF, 9);
+    assert_eq!(regs().run(&[0x8120]).vregs[1], 0b1010);
+    assert_eq!(regs().run(&[0x8121]).vregs[1], 0b1110);
+    assert_eq!(regs().run(&[0x8122]).vregs[1], 0b1000);
+    assert_eq!(regs().run(&[0x8123]).vregs[1], 0b0110);
+    // VF only changes under the logic_resets_vf quirk
+    assert_eq!(regs().run(&[0x8121]).vregs[0xF], 9);
+}
+
+#[test]
+fn op_8xy4_sets_carry() {
+    let chip8 = setup().v(1, 0xF0).v(2, 0x20).run(&[0x8124]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0x10, 1));
+
+    let chip8 = setup().v(1, 0x70).v(2, 0x20).v(0xF, 1).run(&[0x8124]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0x90, 0));
+}
+
+#[test]
+fn op_8xy5_sets_not_borrow() {
+    let chip8 = setup().v(1, 0x30).v(2, 0x10).run(&[0x8125]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0x20, 1));
+
+    let chip8 = setup().v(1, 0x10).v(2, 0x30).v(0xF, 1).run(&[0x8125]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0xE0, 0));
+}
+
+#[test]
+fn op_8xy7_sets_not_borrow() {
+    let chip8 = setup().v(1, 0x10).v(2, 0x30).run(&[0x8127]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0x20, 1));
+
+    let chip8 = setup().v(1, 0x30).v(2, 0x10).v(0xF, 1).run(&[0x8127]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0xE0, 0));
+}
+
+#[test]
+fn op_8xy6_and_8xye_shift() {
+    let chip8 = setup().v(1, 0b1000_0101).run(&[0x8126]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0b0100_0010, 1));
+
+    let chip8 = setup().v(1, 0b1000_0100).v(0xF, 1).run(&[0x8126]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0b0100_0010, 0));
+
+    let chip8 = setup().v(1, 0b1000_0001).run(&[0x812E]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0b0000_0010, 1));
+
+    let chip8 = setup().v(1, 0b0100_0010).v(0xF, 1).run(&[0x812E]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0b1000_0100, 0));
+}
+
+#[test]
+fn op_8xy5_and_8xy7_equal_operands_do_not_borrow() {
+    let chip8 = setup().v(1, 0x42).v(2, 0x42).run(&[0x8125]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0, 1));
+
+    let chip8 = setup().v(1, 0x42).v(2, 0x42).run(&[0x8127]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0, 1));
+}
+
+#[test]
+fn op_8xye_sets_vf_to_the_high_bit() {
+    let chip8 = setup().v(1, 0b1000_0000).run(&[0x812E]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0, 1));
+
+    let chip8 = setup().v(1, 0b0000_0001).v(0xF, 1).run(&[0x812E]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0b10, 0));
+}
+
+#[test]
+fn op_8xyn_with_vf_as_x_keeps_the_flag() {
+    // the flag is written last, so it's all that's left in VF
+    let vf = |vf, vy, opcode| setup().v(0xF, vf).v(1, vy).run(&[opcode]).vregs[0xF];
+
+    assert_eq!(vf(0xF0, 0x20, 0x8F14), 1);
+    assert_eq!(vf(0x10, 0x20, 0x8F14), 0);
+    assert_eq!(vf(0x30, 0x10, 0x8F15), 1);
+    assert_eq!(vf(0x10, 0x30, 0x8F15), 0);
+    assert_eq!(vf(0x10, 0x30, 0x8F17), 1);
+    assert_eq!(vf(0x30, 0x10, 0x8F17), 0);
+    assert_eq!(vf(0b10, 0, 0x8F16), 0);
+    assert_eq!(vf(0b01, 0, 0x8F16), 1);
+    assert_eq!(vf(0x80, 0, 0x8F1E), 1);
+    assert_eq!(vf(0x7F, 0, 0x8F1E), 0);
+}
+
+#[test]
+fn op_8xyn_with_vf_as_y_uses_its_old_value() {
+    let chip8 = setup().v(1, 0xFF).v(0xF, 1).run(&[0x81F4]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (0, 1));
+
+    let chip8 = setup().v(1, 5).v(0xF, 1).run(&[0x81F5]);
+    assert_eq!((chip8.vregs[1], chip8.vregs[0xF]), (4, 1));
+}
+
+#[test]
+fn op_annn_and_bnnn() {
+    assert_eq!(setup().run(&[0xA123]).ireg, 0x123);
+    assert_eq!(setup().v(0, 0x10).run(&[0xB300]).program_counter, 0x310);
+}
+
+#[test]
+fn op_cxnn_masks_the_random_byte() {
+    let mut chip8 = Chip8::new(Quirks::default()).with_rng(ScriptedRng::new(&[0b1011_0110]));
+    chip8.execute(0xC30F).unwrap();
+    assert_eq!(chip8.vregs[3], 0b0110);
+}
+
+#[test]
+fn op_dxyn_draws_and_detects_collisions() {
+    let sprite = || setup().i(0x300).ram(0x300, &[0b1100_0000, 0b0100_0000]);
 
-        assert_eq!(chip8.ram[0..FONTSET_SIZE], FONT_SET[0..FONTSET_SIZE]);
+    let chip8 = sprite().v(0, 3).v(1, 2).run(&[0xD012]);
+    assert_eq!(chip8.display[2 * 64 + 3..2 * 64 + 5], [1, 1]);
+    assert_eq!(chip8.display[3 * 64 + 3..3 * 64 + 5], [0, 1]);
+    assert_eq!(chip8.vregs[0xF], 0);
+
+    // drawing it again erases it and reports the collision
+    let chip8 = sprite().run(&[0xD012, 0xD012]);
+    assert!(chip8.display.iter().all(|&pixel| pixel == 0));
+    assert_eq!(chip8.vregs[0xF], 1);
+}
+
+#[test]
+fn op_dxyn_wraps() {
+    // the starting position wraps, so x = 65 draws at column 1
+    let chip8 = setup().v(0, 65).i(0x300).ram(0x300, &[0x80]).run(&[0xD011]);
+    assert_eq!(chip8.display[1], 1);
+
+    // sprites running off the right edge wrap around by default
+    let chip8 = setup().v(0, 63).i(0x300).ram(0x300, &[0xC0]).run(&[0xD011]);
+    assert_eq!((chip8.display[63], chip8.display[0]), (1, 1));
+}
+
+#[test]
+fn op_ex9e_and_exa1() {
+    let pc_after = |setup: Setup, opcode| setup.v(1, 0xA).run(&[opcode]).program_counter;
+
+    assert_eq!(pc_after(setup().key(0xA), 0xE19E), 0x204);
+    assert_eq!(pc_after(setup(), 0xE19E), 0x202);
+    assert_eq!(pc_after(setup(), 0xE1A1), 0x204);
+    assert_eq!(pc_after(setup().key(0xA), 0xE1A1), 0x202);
+}
+
+#[test]
+fn op_timers() {
+    let chip8 = setup().v(1, 30).v(2, 40).run(&[0xF115, 0xF218, 0xF307]);
+    assert_eq!(chip8.delay_timer, 30);
+    assert_eq!(chip8.sound_timer, 40);
+    assert_eq!(chip8.vregs[3], 30);
+}
+
+#[test]
+fn op_fx0a_blocks_until_a_key_is_pressed_and_released() {
+    let mut chip8 = setup().run(&[0xF30A, 0xF30A, 0xF30A]);
+    assert_eq!(chip8.program_counter, 0x200);
+
+    chip8.keypress(0x7, true).unwrap();
+    chip8.tick().unwrap();
+    assert_eq!(chip8.program_counter, 0x200);
+
+    chip8.keypress(0x7, false).unwrap();
+    chip8.tick().unwrap();
+    assert_eq!(chip8.vregs[3], 0x7);
+    assert_eq!(chip8.program_counter, 0x202);
+}
+
+#[test]
+fn op_fx1e_adds_to_i() {
+    assert_eq!(setup().v(1, 0x20).i(0x2F0).run(&[0xF11E]).ireg, 0x310);
+}
+
+#[test]
+fn op_fx29_points_at_the_font() {
+    let chip8 = setup().v(1, 2).run(&[0xF129]);
+    let i = chip8.ireg as usize;
+    assert_eq!(chip8.ram[i..i + 5], FONT_SET[10..15]);
+}
+
+#[test]
+fn op_fx33_stores_bcd() {
+    for (value, digits) in [
+        (0, [0, 0, 0]),
+        (9, [0, 0, 9]),
+        (42, [0, 4, 2]),
+        (100, [1, 0, 0]),
+        (199, [1, 9, 9]),
+        (255, [2, 5, 5]),
+    ] {
+        let chip8 = setup().v(1, value).i(0x300).run(&[0xF133]);
+        assert_eq!(chip8.ram[0x300..0x303], digits, "{}", value);
     }
 }
+
+#[test]
+fn op_fx55_and_fx65() {
+    let chip8 = setup()
+        .v(0, 1)
+        .v(1, 2)
+        .v(2, 3)
+        .v(3, 4)
+        .i(0x300)
+        .run(&[0xF255]);
+    assert_eq!(chip8.ram[0x300..0x304], [1, 2, 3, 0]);
+    assert_eq!(chip8.ireg, 0x300);
+
+    let chip8 = setup().i(0x300).ram(0x300, &[9, 8, 7]).run(&[0xF165]);
+    assert_eq!(chip8.vregs[..3], [9, 8, 0]);
+    assert_eq!(chip8.ireg, 0x300);
+}
@@ -0,0 +1,186 @@
+/*
+TRACE FORMATS (for reference):
+
+Text, one line per instruction, registers as they are after it ran:
+
+   CYCLE   PC   OP    MNEMONIC             V0 .. VF                                          I
+       0 0200 6A02 LD VA, 0x02        00 00 00 00 00 00 00 00 00 00 02 00 00 00 00 00 I=0000
+
+  cycle right aligned in 8 columns (wider once it outgrows them), PC and
+  opcode as 4 hex digits, the mnemonic padded to 18 columns, then the
+  registers and I in hex. Opcodes that don't decode show as "???". An
+  XO-CHIP long load shows the word it loads, "LD I, LONG 0x1234".
+
+Binary, big endian: the magic "C8TR" and a version byte (1), then one
+30 byte record per instruction:
+
+  OFFSET  SIZE  CONTENT
+       0     8  cycle
+       8     2  PC
+      10     2  opcode
+      12    16  V0-VF
+      28     2  I
+
+A long load's word isn't in the record: the I that follows it is that word.
+*/
+
+use std::io::{self, Write};
+use std::ops::{Range, RangeInclusive};
+
+use crate::decode::Instruction;
+
+pub const TRACE_VERSION: u8 = 1;
+
+/// One executed instruction. `cycle` counts instructions from power on, so
+/// the first one is cycle 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub vregs: [u8; 16],
+    pub ireg: u16,
+    // the word after an XO-CHIP F000 NNNN long load
+    pub long: Option<u16>,
+}
+
+impl TraceEntry {
+    pub fn to_text(&self) -> String {
+        let mnemonic = match (Instruction::decode(self.opcode), self.long) {
+            (Some(Instruction::LoadLongI), Some(word)) => format!("LD I, LONG {:#06X}", word),
+            (Some(instruction), _) => instruction.to_string(),
+            (None, _) => "???".to_string(),
+        };
+        let vregs: Vec<String> = self.vregs.iter().map(|v| format!("{:02X}", v)).collect();
+        format!(
+            "{:>8} {:04X} {:04X} {:<18} {} I={:04X}",
+            self.cycle,
+            self.pc,
+            self.opcode,
+            mnemonic,
+            vregs.join(" "),
+            self.ireg
+        )
+    }
+}
+
+/// Receives every instruction `Chip8::tick` executes. Set one with
+/// `Chip8::with_tracer`.
+pub trait Tracer {
+    fn trace(&mut self, entry: &TraceEntry);
+
+    /// Flushes whatever was buffered and reports the first write error, if
+    /// any happened along the way.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Limits which instructions get recorded. Both limits must match.
+#[derive(Clone, Debug, Default)]
+pub struct TraceFilter {
+    pub addrs: Option<RangeInclusive<u16>>,
+    pub cycles: Option<Range<u64>>,
+}
+
+impl TraceFilter {
+    pub fn matches(&self, entry: &TraceEntry) -> bool {
+        self.addrs
+            .as_ref()
+            .is_none_or(|addrs| addrs.contains(&entry.pc))
+            && self
+                .cycles
+                .as_ref()
+                .is_none_or(|cycles| cycles.contains(&entry.cycle))
+    }
+}
+
+// shared by both writers: keeps the first error and stops writing after it
+struct Output<W: Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: Write> Output<W> {
+    fn write(&mut self, bytes: &[u8]) {
+        if self.error.is_none() {
+            if let Err(e) = self.writer.write_all(bytes) {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => self.writer.flush(),
+        }
+    }
+}
+
+/// Writes the text format. Wrap files in a `BufWriter`.
+pub struct TextTracer<W: Write> {
+    output: Output<W>,
+    filter: TraceFilter,
+}
+
+impl<W: Write> TextTracer<W> {
+    pub fn new(writer: W, filter: TraceFilter) -> Self {
+        TextTracer {
+            output: Output {
+                writer,
+                error: None,
+            },
+            filter,
+        }
+    }
+}
+
+impl<W: Write> Tracer for TextTracer<W> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        if self.filter.matches(entry) {
+            self.output
+                .write(format!("{}\n", entry.to_text()).as_bytes());
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.output.finish()
+    }
+}
+
+/// Writes the binary format. Wrap files in a `BufWriter`.
+pub struct BinaryTracer<W: Write> {
+    output: Output<W>,
+    filter: TraceFilter,
+}
+
+impl<W: Write> BinaryTracer<W> {
+    pub fn new(writer: W, filter: TraceFilter) -> Self {
+        let mut output = Output {
+            writer,
+            error: None,
+        };
+        output.write(b"C8TR");
+        output.write(&[TRACE_VERSION]);
+        BinaryTracer { output, filter }
+    }
+}
+
+impl<W: Write> Tracer for BinaryTracer<W> {
+    fn trace(&mut self, entry: &TraceEntry) {
+        if self.filter.matches(entry) {
+            let mut record = Vec::with_capacity(30);
+            record.extend_from_slice(&entry.cycle.to_be_bytes());
+            record.extend_from_slice(&entry.pc.to_be_bytes());
+            record.extend_from_slice(&entry.opcode.to_be_bytes());
+            record.extend_from_slice(&entry.vregs);
+            record.extend_from_slice(&entry.ireg.to_be_bytes());
+            self.output.write(&record);
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.output.finish()
+    }
+}
@@ -0,0 +1,74 @@
+//! Runs the chip8-headless binary the way scripts and CI use it.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{self, Command, Output};
+
+use chip8::asm::assemble;
+
+// exits on its 37th instruction, part way through the fourth frame at the
+// default 10 instructions per frame
+const EXITS_MID_FRAME: &str = "
+        LD V0, 0
+loop:   ADD V0, 1
+        SE V0, 12
+        JP loop
+        EXIT
+";
+
+// a scratch directory for one test's files
+fn scratch(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("chip8-headless-{}-{}", name, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn headless(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .args(args)
+        .args(["--display", "none"])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn movie_ending_on_exit_plays_back() {
+    let dir = scratch("exit");
+    let rom = dir.join("exit.ch8");
+    let movie = dir.join("exit.c8m");
+    fs::write(&rom, assemble(EXITS_MID_FRAME).unwrap().bytes).unwrap();
+    let rom = rom.to_str().unwrap();
+    let movie = movie.to_str().unwrap();
+
+    let recorded = headless(&[rom, "--frames", "10", "--record", movie]);
+    assert!(recorded.status.success());
+    let played = headless(&[rom, "--play", movie]);
+    assert!(
+        played.status.success(),
+        "{}",
+        String::from_utf8_lossy(&played.stderr)
+    );
+
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn movies_refuse_cycle_limits() {
+    let dir = scratch("cycles");
+    let rom = dir.join("exit.ch8");
+    fs::write(&rom, assemble(EXITS_MID_FRAME).unwrap().bytes).unwrap();
+    let movie = dir.join("cycles.c8m");
+
+    let output = headless(&[
+        rom.to_str().unwrap(),
+        "--cycles",
+        "15",
+        "--record",
+        movie.to_str().unwrap(),
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(!movie.exists());
+
+    fs::remove_dir_all(dir).ok();
+}
@@ -0,0 +1,252 @@
+//! Runs test ROMs headlessly and compares the final display with a golden
+//! framebuffer in `tests/golden`, drawn the way `display_ascii` draws it.
+//!
+//! ROMs come either from the repo's `ROMs` directory or as assembly in
+//! `tests/roms`, assembled on the fly.
+//!
+//! The cases for Timendus' chip8-test-suite (MIT licensed) are ignored until
+//! its ROMs are vendored: they aren't in the repo yet, and goldens can only
+//! be drawn from the real ROMs. To enable them, copy the suite's `bin/*.ch8`
+//! into `tests/roms/timendus` along with its LICENSE, run
+//! `UPDATE_GOLDEN=1 cargo test --test roms -- --ignored`, check every
+//! golden shows the suite's passing screen, and drop the `#[ignore]`s.
+//!
+//! After an intended change to what a ROM draws, rerun with
+//! `UPDATE_GOLDEN=1` to rewrite the goldens and review the diff.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chip8::asm::assemble_file;
+use chip8::chip8::Chip8;
+use chip8::dump::display_ascii;
+use chip8::mode::Mode;
+use chip8::quirks::Quirks;
+use chip8::scheduler::Scheduler;
+
+enum Rom {
+    // a binary, relative to the repo root
+    Binary(&'static str),
+    // assembly, relative to tests/roms
+    Source(&'static str),
+}
+
+struct Case {
+    name: &'static str,
+    rom: Rom,
+    quirks: Quirks,
+    mode: Mode,
+    frames: u64,
+    // (frame, key, pressed)
+    keys: &'static [(u64, usize, bool)],
+    // (address, value) written after loading, e.g. the suite's menu choice
+    pokes: &'static [(usize, u8)],
+    // whether the buzzer has to have sounded, or None to not check
+    beeps: Option<bool>,
+}
+
+impl Case {
+    fn new(name: &'static str, rom: Rom, frames: u64) -> Self {
+        Case {
+            name,
+            rom,
+            quirks: Quirks::default(),
+            mode: Mode::Classic,
+            frames,
+            keys: &[],
+            pokes: &[],
+            beeps: None,
+        }
+    }
+}
+
+fn manifest_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+fn load(rom: &Rom) -> Vec<u8> {
+    match rom {
+        Rom::Binary(path) => {
+            let path = manifest_dir().join("..").join(path);
+            fs::read(&path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e))
+        }
+        Rom::Source(path) => {
+            let path = manifest_dir().join("tests/roms").join(path);
+            assemble_file(&path)
+                .unwrap_or_else(|e| panic!("{}", e))
+                .bytes
+        }
+    }
+}
+
+// the final display, and whether the buzzer sounded along the way
+fn run(case: &Case) -> (String, bool) {
+    let mut chip8 = Chip8::new(case.quirks).with_mode(case.mode).with_seed(0);
+    chip8.load_fonts();
+    chip8.load_rom(&load(&case.rom)).unwrap();
+    for &(addr, value) in case.pokes {
+        chip8.ram[addr] = value;
+    }
+    let mut beeped = false;
+
+    // 600 instructions a second, what the frontends run by default
+    let mut scheduler = Scheduler::default();
+    for frame in 0..case.frames {
+        for &(_, key, pressed) in case.keys.iter().filter(|(at, ..)| *at == frame) {
+            chip8.keypress(key, pressed).unwrap();
+        }
+        if chip8.exited {
+            break;
+        }
+        scheduler
+            .run_frame(&mut chip8)
+            .unwrap_or_else(|e| panic!("{}: {}", case.name, e));
+        beeped |= chip8.is_sound_active();
+    }
+
+    (display_ascii(&chip8), beeped)
+}
+
+fn check(case: Case) {
+    let (actual, beeped) = run(&case);
+    if let Some(beeps) = case.beeps {
+        assert_eq!(beeped, beeps, "{}: buzzer", case.name);
+    }
+    let golden = manifest_dir()
+        .join("tests/golden")
+        .join(format!("{}.txt", case.name));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&golden, &actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&golden).unwrap_or_else(|e| {
+        panic!(
+            "{}: {}, run with UPDATE_GOLDEN=1 to create it",
+            relative(&golden),
+            e
+        )
+    });
+    assert!(
+        actual == expected,
+        "{} no longer matches {}\n\nexpected:\n{}\nactual:\n{}",
+        case.name,
+        relative(&golden),
+        expected,
+        actual
+    );
+}
+
+fn relative(path: &Path) -> String {
+    path.strip_prefix(manifest_dir())
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+#[test]
+fn chip8_logo() {
+    check(Case::new(
+        "chip8_logo",
+        Rom::Binary("ROMs/CHIP8-Logo.ch8"),
+        60,
+    ));
+}
+
+#[test]
+fn flags() {
+    check(Case::new("flags", Rom::Source("flags.asm"), 60));
+}
+
+#[test]
+fn font_and_bcd() {
+    check(Case::new("font", Rom::Source("font.asm"), 60));
+}
+
+#[test]
+fn pong_with_input() {
+    // CXNN serves the ball, so this also covers the seeded RNG
+    check(Case {
+        keys: &[(30, 0x1, true), (90, 0x1, false)],
+        ..Case::new("pong", Rom::Binary("ROMs/Pong (1 player).ch8"), 120)
+    });
+}
+
+// Timendus' chip8-test-suite, see the top of the file. Writing to 0x1FF picks
+// an entry from a test's menu without going through the keypad
+
+#[test]
+#[ignore = "Timendus' chip8-test-suite is not vendored yet"]
+fn suite_ibm_logo() {
+    check(Case::new(
+        "timendus_ibm_logo",
+        Rom::Binary("chip8/tests/roms/timendus/2-ibm-logo.ch8"),
+        60,
+    ));
+}
+
+#[test]
+#[ignore = "Timendus' chip8-test-suite is not vendored yet"]
+fn suite_corax_plus() {
+    check(Case::new(
+        "timendus_corax_plus",
+        Rom::Binary("chip8/tests/roms/timendus/3-corax+.ch8"),
+        60,
+    ));
+}
+
+#[test]
+#[ignore = "Timendus' chip8-test-suite is not vendored yet"]
+fn suite_flags() {
+    check(Case::new(
+        "timendus_flags",
+        Rom::Binary("chip8/tests/roms/timendus/4-flags.ch8"),
+        120,
+    ));
+}
+
+#[test]
+#[ignore = "Timendus' chip8-test-suite is not vendored yet"]
+fn suite_quirks() {
+    // 1 runs the tests for the original CHIP-8
+    check(Case {
+        quirks: Quirks::cosmac_vip(),
+        pokes: &[(0x1FF, 1)],
+        ..Case::new(
+            "timendus_quirks",
+            Rom::Binary("chip8/tests/roms/timendus/5-quirks.ch8"),
+            600,
+        )
+    });
+}
+
+#[test]
+#[ignore = "Timendus' chip8-test-suite is not vendored yet"]
+fn suite_keypad() {
+    // 3 is the FX0A test, which only takes a key once it's let go
+    check(Case {
+        keys: &[(60, 0x5, true), (90, 0x5, false)],
+        pokes: &[(0x1FF, 3)],
+        ..Case::new(
+            "timendus_keypad",
+            Rom::Binary("chip8/tests/roms/timendus/6-keypad.ch8"),
+            150,
+        )
+    });
+}
+
+#[test]
+#[ignore = "Timendus' chip8-test-suite is not vendored yet"]
+fn suite_beep() {
+    // the buzzer sounds while B is held
+    check(Case {
+        keys: &[(30, 0xB, true), (60, 0xB, false)],
+        beeps: Some(true),
+        ..Case::new(
+            "timendus_beep",
+            Rom::Binary("chip8/tests/roms/timendus/7-beep.ch8"),
+            90,
+        )
+    });
+}
@@ -0,0 +1,141 @@
+; VF after the arithmetic and shift opcodes. Each result is drawn as three
+; hex digits, VX then VF, four to a row:
+;
+;   row 1   8XY4 no carry, 8XY4 carry, 8XY5 no borrow, 8XY5 borrow
+;   row 2   8XY5 equal, 8XY7 no borrow, 8XY7 borrow, 8XY7 equal
+;   row 3   8XY6 and 8XYE shifting out a 1, then a 0
+;   row 4   8XY4, 8XY5, 8XY7 and 8XYE with VF as X, only the flag survives
+;   row 5   OR, AND, XOR and 7XNN leave VF (preset to 5) alone
+
+        LD V6, 0
+        LD V7, 0
+
+        LD V1, 0x12
+        LD V2, 0x34
+        ADD V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0xF0
+        LD V2, 0x20
+        ADD V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0x30
+        LD V2, 0x10
+        SUB V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0x10
+        LD V2, 0x30
+        SUB V1, V2
+        LD V3, VF
+        CALL show
+
+        LD V1, 0x42
+        LD V2, 0x42
+        SUB V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0x10
+        LD V2, 0x30
+        SUBN V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0x30
+        LD V2, 0x10
+        SUBN V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0x42
+        LD V2, 0x42
+        SUBN V1, V2
+        LD V3, VF
+        CALL show
+
+        LD V1, 0x85
+        SHR V1
+        LD V3, VF
+        CALL show
+        LD V1, 0x84
+        SHR V1
+        LD V3, VF
+        CALL show
+        LD V1, 0x81
+        SHL V1
+        LD V3, VF
+        CALL show
+        LD V1, 0x42
+        SHL V1
+        LD V3, VF
+        CALL show
+
+        LD VF, 0xF0
+        LD V2, 0x20
+        ADD VF, V2
+        LD V1, VF
+        LD V3, VF
+        CALL show
+        LD VF, 0x30
+        LD V2, 0x10
+        SUB VF, V2
+        LD V1, VF
+        LD V3, VF
+        CALL show
+        LD VF, 0x30
+        LD V2, 0x10
+        SUBN VF, V2
+        LD V1, VF
+        LD V3, VF
+        CALL show
+        LD VF, 0x80
+        SHL VF
+        LD V1, VF
+        LD V3, VF
+        CALL show
+
+        LD V1, 0x0C
+        LD V2, 0x0A
+        LD VF, 5
+        OR V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0x0C
+        LD VF, 5
+        AND V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0x0C
+        LD VF, 5
+        XOR V1, V2
+        LD V3, VF
+        CALL show
+        LD V1, 0xFE
+        LD VF, 5
+        ADD V1, 3
+        LD V3, VF
+        CALL show
+
+done:   JP done
+
+; draws V1 as two hex digits and V3 as one at (V6, V7), then moves along
+show:   LD V4, V1
+        SHR V4
+        SHR V4
+        SHR V4
+        SHR V4
+        LD F, V4
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD V4, 0x0F
+        AND V4, V1
+        LD F, V4
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V3
+        DRW V6, V7, 5
+        ADD V6, 6
+        SE V6, 64
+        RET
+        LD V6, 0
+        ADD V7, 6
+        RET
@@ -0,0 +1,34 @@
+; The sixteen FX29 glyphs in two rows, then FX33 of 254 as three digits.
+
+        LD V0, 0
+        LD V6, 0
+        LD V7, 0
+glyph:  LD F, V0
+        DRW V6, V7, 5
+        ADD V0, 1
+        ADD V6, 5
+        SE V0, 8
+        JP next
+        LD V6, 0
+        LD V7, 6
+next:   SE V0, 16
+        JP glyph
+
+        LD V0, 254
+        LD I, digits
+        LD B, V0
+        LD V2, [I]
+        LD V6, 0
+        LD V7, 14
+        LD F, V0
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V1
+        DRW V6, V7, 5
+        ADD V6, 5
+        LD F, V2
+        DRW V6, V7, 5
+
+done:   JP done
+
+digits: DB 0, 0, 0
@@ -0,0 +1,119 @@
+use std::f32::consts::TAU;
+
+use sdl2::audio::{AudioCallback, AudioDevice, AudioSpecDesired};
+use sdl2::Sdl;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AudioSettings {
+    /// Tone frequency in Hz.
+    pub frequency: f32,
+    /// 0.0 is silent, 1.0 is full scale.
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+pub struct Tone {
+    // fraction of a period advanced per sample
+    phase_inc: f32,
+    phase: f32,
+    volume: f32,
+    waveform: Waveform,
+}
+
+impl AudioCallback for Tone {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            let wave = match self.waveform {
+                Waveform::Square => {
+                    if self.phase < 0.5 {
+                        1.0
+                    } else {
+                        -1.0
+                    }
+                }
+                Waveform::Sine => (self.phase * TAU).sin(),
+                Waveform::Triangle => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            };
+            *sample = wave * self.volume;
+            self.phase = (self.phase + self.phase_inc) % 1.0;
+        }
+    }
+}
+
+/// The buzzer. The device stays open and is paused whenever the sound timer
+/// is zero or the user has muted it.
+pub struct Audio {
+    device: AudioDevice<Tone>,
+    playing: bool,
+    pub muted: bool,
+}
+
+impl Audio {
+    pub fn new(sdl_context: &Sdl, settings: AudioSettings) -> Result<Self, String> {
+        let audio_subsystem = sdl_context.audio()?;
+        let desired = AudioSpecDesired {
+            freq: Some(44100),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = audio_subsystem.open_playback(None, &desired, |spec| Tone {
+            phase_inc: settings.frequency / spec.freq as f32,
+            phase: 0.0,
+            volume: settings.volume.clamp(0.0, 1.0),
+            waveform: settings.waveform,
+        })?;
+
+        Ok(Audio {
+            device,
+            playing: false,
+            muted: false,
+        })
+    }
+
+    /// Starts or stops the tone to follow `Chip8::is_sound_active`.
+    pub fn update(&mut self, active: bool) {
+        let play = active && !self.muted;
+        if play != self.playing {
+            if play {
+                self.device.resume();
+            } else {
+                self.device.pause();
+            }
+            self.playing = play;
+        }
+    }
+
+    pub fn toggle_mute(&mut self) {
+        self.muted = !self.muted;
+    }
+}
@@ -0,0 +1,241 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chip8::constants::MAX_FONT_BASE;
+use chip8::debugger::parse_address;
+use chip8::mode::Mode;
+use chip8::quirks::Quirks;
+use chip8::rewind::{DEFAULT_REWIND_BYTES, DEFAULT_REWIND_FRAMES};
+use chip8::scheduler::{DEFAULT_IPS, FRAME_RATE};
+use sdl2::pixels::Color;
+
+use crate::audio::{AudioSettings, Waveform};
+use crate::renderer::{palette_from_name, DEFAULT_PALETTE};
+
+pub const USAGE: &str = "Usage: renderer [rom] [options]
+
+With no ROM, pick one from the bundled ROMs directory: the first ROMs
+directory found in the working directory, the executable's directory or
+any of their parents. CHIP8_ROMS overrides it.
+
+Options:
+  --scale <n>           window pixels per CHIP-8 pixel (default 15)
+  --ips <n>             instructions per second (default 600)
+  --quirks <preset>     default, vip, chip48 or schip
+  --mode <mode>         classic or xochip (default: xochip for .xo8 files)
+  --fullscreen          start fullscreen
+  --no-vsync            don't wait for vsync, timing is unaffected
+  --palette <colors>    mono, amber, green or lcd, or four hex colors
+                        like 000000,ffffff,aaaaaa,555555
+  --seed <n>            seed for CXNN, for reproducible runs
+  --font <file>         custom font, 80 bytes of 4x5 glyphs optionally
+                        followed by 160 bytes of 8x10 ones
+  --font-base <addr>    where the font goes in memory (default 0x050)
+  --mute                start with sound off (M toggles)
+  --tone <hz>           buzzer frequency (default 440)
+  --volume <0-100>      buzzer volume (default 25)
+  --waveform <shape>    square, sine or triangle
+  --keys <file>         keyboard and controller bindings, TOML (default
+                        keys.toml next to the ROMs directory). F8 rebinds
+                        keys for this ROM, Shift+F8 for every ROM
+  --turbo <n>           speed while Tab is held, 0 for as fast as
+                        possible (default 4)
+  --rewind <seconds>    how far Backspace can rewind, 0 to turn it off
+                        (default 10)
+  --rewind-mb <n>       memory the rewind history may use (default 16)
+  --record <file>       record the keys pressed into a movie, written on
+                        exit
+  --play <file>         play a movie back with the settings it was
+                        recorded with, then hand over to the keyboard";
+
+// the ROMs that ship with the repo, looked up at runtime so the binary
+// works wherever it's started from and wherever it was built
+fn roms_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("CHIP8_ROMS") {
+        return PathBuf::from(dir);
+    }
+    let cwd = env::current_dir().ok();
+    let exe_dir = env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf));
+    cwd.iter()
+        .chain(exe_dir.iter())
+        .flat_map(|dir| dir.ancestors())
+        .map(|dir| dir.join("ROMs"))
+        .find(|dir| dir.is_dir())
+        .unwrap_or_else(|| PathBuf::from("ROMs"))
+}
+
+// the key bindings live next to the ROMs, so they're found the same way
+fn keys_file() -> PathBuf {
+    roms_dir().with_file_name("keys.toml")
+}
+
+pub struct Options {
+    pub rom: Option<PathBuf>,
+    pub scale: u32,
+    pub ips: u64,
+    pub quirks: Quirks,
+    pub mode: Option<Mode>,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    pub palette: [Color; 4],
+    pub seed: Option<u64>,
+    pub font: Option<PathBuf>,
+    pub font_base: Option<u16>,
+    pub mute: bool,
+    pub audio: AudioSettings,
+    pub keys: PathBuf,
+    pub turbo: u32,
+    pub rewind_frames: usize,
+    pub rewind_bytes: usize,
+    pub record: Option<PathBuf>,
+    pub play: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            rom: None,
+            scale: 15,
+            ips: DEFAULT_IPS,
+            quirks: Quirks::default(),
+            mode: None,
+            fullscreen: false,
+            vsync: true,
+            palette: DEFAULT_PALETTE,
+            seed: None,
+            font: None,
+            font_base: None,
+            mute: false,
+            audio: AudioSettings::default(),
+            keys: keys_file(),
+            turbo: 4,
+            rewind_frames: DEFAULT_REWIND_FRAMES,
+            rewind_bytes: DEFAULT_REWIND_BYTES,
+            record: None,
+            play: None,
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: Option<String>) -> Result<T, String> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| format!("{} needs a number", flag))
+}
+
+fn parse_palette(text: &str) -> Option<[Color; 4]> {
+    if let Some(palette) = palette_from_name(text) {
+        return Some(palette);
+    }
+    let colors: Vec<Color> = text
+        .split(',')
+        .map(|hex| {
+            let rgb = u32::from_str_radix(hex.trim().trim_start_matches('#'), 16).ok()?;
+            Some(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+        })
+        .collect::<Option<_>>()?;
+    colors.try_into().ok()
+}
+
+/// Parses the arguments after the program name. `Err` carries a message to
+/// print above the usage.
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    let mut args = args.peekable();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scale" => options.scale = number::<u32>(&arg, args.next())?.max(1),
+            "--ips" => options.ips = number(&arg, args.next())?,
+            "--quirks" => {
+                let name = args.next().unwrap_or_default();
+                options.quirks = Quirks::from_name(&name)
+                    .ok_or_else(|| format!("Unknown quirks preset '{}'", name))?;
+            }
+            "--mode" => {
+                let name = args.next().unwrap_or_default();
+                options.mode =
+                    Some(Mode::from_name(&name).ok_or_else(|| format!("Unknown mode '{}'", name))?);
+            }
+            "--fullscreen" => options.fullscreen = true,
+            "--no-vsync" => options.vsync = false,
+            "--palette" => {
+                let text = args.next().unwrap_or_default();
+                options.palette =
+                    parse_palette(&text).ok_or_else(|| format!("Invalid palette '{}'", text))?;
+            }
+            "--seed" => options.seed = Some(number(&arg, args.next())?),
+            "--font" => options.font = args.next().map(PathBuf::from),
+            "--font-base" => {
+                let addr = args.next().as_deref().and_then(parse_address);
+                match addr {
+                    Some(addr) if addr <= MAX_FONT_BASE => options.font_base = Some(addr),
+                    _ => {
+                        return Err(format!(
+                            "--font-base needs an address up to {:#05x}",
+                            MAX_FONT_BASE
+                        ))
+                    }
+                }
+            }
+            "--mute" => options.mute = true,
+            "--tone" => options.audio.frequency = number(&arg, args.next())?,
+            "--volume" => {
+                options.audio.volume = number::<f32>(&arg, args.next())?.clamp(0.0, 100.0) / 100.0
+            }
+            "--waveform" => {
+                let name = args.next().unwrap_or_default();
+                options.audio.waveform = Waveform::from_name(&name)
+                    .ok_or_else(|| format!("Unknown waveform '{}'", name))?;
+            }
+            "--keys" => options.keys = PathBuf::from(args.next().unwrap_or_default()),
+            "--turbo" => options.turbo = number(&arg, args.next())?,
+            "--rewind" => {
+                options.rewind_frames = number::<usize>(&arg, args.next())? * FRAME_RATE as usize
+            }
+            "--rewind-mb" => options.rewind_bytes = number::<usize>(&arg, args.next())? << 20,
+            "--record" => options.record = args.next().map(PathBuf::from),
+            "--play" => options.play = args.next().map(PathBuf::from),
+            _ if options.rom.is_none() && !arg.starts_with("--") => {
+                options.rom = Some(PathBuf::from(arg))
+            }
+            _ => return Err(format!("Unexpected argument '{}'", arg)),
+        }
+    }
+
+    if options.record.is_some() && options.play.is_some() {
+        return Err("--record and --play can't be used together".to_string());
+    }
+    Ok(options)
+}
+
+/// Lists the bundled ROMs and asks for one on stdin.
+pub fn pick_rom() -> Result<PathBuf, String> {
+    let mut rom_files: Vec<PathBuf> = fs::read_dir(roms_dir())
+        .map_err(|_| "Could not open the ROMs directory.".to_string())?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .collect();
+    rom_files.sort();
+
+    println!("Available ROMs:");
+    for (index, path) in rom_files.iter().enumerate() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        println!("{}: {}", index + 1, name);
+    }
+
+    println!("Enter the number of the ROM to load:");
+    let mut input = String::new();
+    io::stdin()
+        .read_line(&mut input)
+        .map_err(|_| "Failed to read input".to_string())?;
+    match input.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= rom_files.len() => Ok(rom_files.swap_remove(num - 1)),
+        _ => Err("Invalid selection. Exiting.".to_string()),
+    }
+}
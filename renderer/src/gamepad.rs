@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use sdl2::controller::{Axis, Button, GameController};
+use sdl2::event::Event;
+use sdl2::{GameControllerSubsystem, Sdl};
+
+use crate::input::{apply_table, rom_key, HeldKeys, HostInput, KeyConfig, KeyTable};
+
+pub const DEFAULT_DEADZONE: f32 = 0.25;
+
+// D-pad and left stick go to the keys under WASD in the default keyboard
+// layout, which is what most games steer with
+const DEFAULT_PAD: [(usize, &[&str]); 6] = [
+    (0x5, &["dpup", "lefty-"]),
+    (0x8, &["dpdown", "lefty+"]),
+    (0x7, &["dpleft", "leftx-"]),
+    (0x9, &["dpright", "leftx+"]),
+    (0x6, &["a"]),
+    (0x4, &["b"]),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PadInput {
+    Button(Button),
+    // true for the positive direction
+    Axis(Axis, bool),
+}
+
+impl PadInput {
+    fn from_name(name: &str) -> Result<PadInput, String> {
+        let input = match name.strip_suffix(['+', '-']) {
+            Some(axis) => {
+                Axis::from_string(axis).map(|axis| PadInput::Axis(axis, name.ends_with('+')))
+            }
+            None => Button::from_string(name).map(PadInput::Button),
+        };
+        input.ok_or_else(|| format!("Unknown controller input '{}'", name))
+    }
+}
+
+/// Controller inputs to CHIP-8 keys, layered like `KeyBindings`.
+pub struct PadBindings {
+    inputs: HashMap<PadInput, usize>,
+    deadzone: i16,
+}
+
+impl PadBindings {
+    pub fn from_config(config: &KeyConfig, rom_hash: u64) -> Result<PadBindings, String> {
+        let deadzone = config.deadzone.unwrap_or(DEFAULT_DEADZONE).clamp(0.0, 1.0);
+        let mut bindings = PadBindings {
+            inputs: HashMap::new(),
+            deadzone: (deadzone * i16::MAX as f32) as i16,
+        };
+
+        let defaults: KeyTable = DEFAULT_PAD
+            .iter()
+            .map(|(key, names)| {
+                let names = names.iter().map(|name| name.to_string()).collect();
+                (format!("{:X}", key), names)
+            })
+            .collect();
+        bindings.apply(&defaults)?;
+        bindings.apply(&config.pad)?;
+        if let Some(rom) = config.roms.get(&rom_key(rom_hash)) {
+            bindings.apply(&rom.pad)?;
+        }
+        Ok(bindings)
+    }
+
+    fn apply(&mut self, table: &KeyTable) -> Result<(), String> {
+        apply_table(&mut self.inputs, table, PadInput::from_name)
+    }
+}
+
+/// The controllers plugged in. SDL reports controllers already connected
+/// at startup as added too, so they all go through `handle`.
+pub struct Gamepads {
+    subsystem: GameControllerSubsystem,
+    controllers: HashMap<u32, GameController>,
+    bindings: PadBindings,
+}
+
+impl Gamepads {
+    pub fn new(sdl: &Sdl, bindings: PadBindings) -> Result<Gamepads, String> {
+        Ok(Gamepads {
+            subsystem: sdl.game_controller()?,
+            controllers: HashMap::new(),
+            bindings,
+        })
+    }
+
+    /// Opens and closes controllers as they come and go, and turns their
+    /// input into CHIP-8 key presses (true) and releases (false). `held` is
+    /// shared with the keyboard, so neither lets go of the other's keys.
+    pub fn handle(&mut self, event: &Event, held: &mut HeldKeys<HostInput>) -> Vec<(usize, bool)> {
+        match *event {
+            Event::ControllerDeviceAdded { which, .. } => {
+                match self.subsystem.open(which) {
+                    Ok(controller) => {
+                        println!("Controller connected: {}", controller.name());
+                        self.controllers
+                            .insert(controller.instance_id(), controller);
+                    }
+                    Err(e) => println!("Could not open controller {}: {}", which, e),
+                }
+                Vec::new()
+            }
+            Event::ControllerDeviceRemoved { which, .. } => {
+                if let Some(controller) = self.controllers.remove(&which) {
+                    println!("Controller disconnected: {}", controller.name());
+                }
+                // let go of everything it was holding
+                held.release_where(|input| matches!(*input, HostInput::Pad(id, _) if id == which))
+                    .into_iter()
+                    .map(|key| (key, false))
+                    .collect()
+            }
+            Event::ControllerButtonDown { which, button, .. } => self
+                .press(held, which, PadInput::Button(button))
+                .into_iter()
+                .collect(),
+            Event::ControllerButtonUp { which, button, .. } => {
+                release(held, which, PadInput::Button(button))
+                    .into_iter()
+                    .collect()
+            }
+            Event::ControllerAxisMotion {
+                which, axis, value, ..
+            } => {
+                // None while inside the deadzone, else whether it's positive
+                let pushed =
+                    (value.unsigned_abs() > self.bindings.deadzone as u16).then_some(value > 0);
+                let mut changes: Vec<(usize, bool)> = [true, false]
+                    .into_iter()
+                    .filter(|&positive| pushed != Some(positive))
+                    .filter_map(|positive| release(held, which, PadInput::Axis(axis, positive)))
+                    .collect();
+                if let Some(positive) = pushed {
+                    changes.extend(self.press(held, which, PadInput::Axis(axis, positive)));
+                }
+                changes
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    // the key to press, if the input is bound and nothing was holding it yet
+    fn press(
+        &self,
+        held: &mut HeldKeys<HostInput>,
+        which: u32,
+        input: PadInput,
+    ) -> Option<(usize, bool)> {
+        let key = *self.bindings.inputs.get(&input)?;
+        held.press(key, HostInput::Pad(which, input))
+            .then_some((key, true))
+    }
+}
+
+// the key to release, if the input was the last thing holding it
+fn release(held: &mut HeldKeys<HostInput>, which: u32, input: PadInput) -> Option<(usize, bool)> {
+    held.release(&HostInput::Pad(which, input))
+        .map(|key| (key, false))
+}
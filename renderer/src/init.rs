@@ -5,17 +5,21 @@ pub struct InitSdlReturn {
     pub canvas: Canvas<Window>,
 }
 
-pub fn init_sdl(w: u32, h: u32) -> InitSdlReturn {
+pub fn init_sdl(w: u32, h: u32, vsync: bool, fullscreen: bool) -> InitSdlReturn {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window("Rohit's Rust CHIP8 Emulator", w, h)
-        .position_centered()
-        .opengl()
-        .build()
-        .unwrap();
+    let mut builder = video_subsystem.window("Rohit's Rust CHIP8 Emulator", w, h);
+    builder.position_centered().opengl();
+    if fullscreen {
+        builder.fullscreen_desktop();
+    }
+    let window = builder.build().unwrap();
 
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    let mut builder = window.into_canvas();
+    if vsync {
+        builder = builder.present_vsync();
+    }
+    let mut canvas = builder.build().unwrap();
     canvas.clear();
     canvas.present();
 
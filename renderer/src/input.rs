@@ -1,23 +1,349 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::hash::Hash;
+use std::io;
+use std::path::Path;
+
+use chip8::constants::KEYBOARD_MAP_SIZE;
 use sdl2::keyboard::Keycode;
+use serde::{Deserialize, Serialize};
+
+use crate::gamepad::PadInput;
+
+/*
+KEY BINDINGS FILE (for reference):
+
+CHIP-8 keys 0-F, each bound to any number of SDL key names under [keys], or
+controller inputs under [pad]. Controller inputs are SDL button names (a,
+dpup, leftshoulder, ...) or an axis name with a direction (leftx-, lefty+,
+righttrigger+, ...). Keys left out keep the layout below them: a ROM's
+tables fall back to the global ones, which fall back to the built-in
+layouts. ROMs are keyed by the FNV-1a hash of the ROM file in hex, the name
+is only there for people reading the file. The deadzone is how far from
+center a stick has to move to count, as a fraction of its full travel.
+
+  deadzone = 0.3
+
+  [keys]
+  5 = ["W", "Up"]
+  8 = ["S", "Down"]
+
+  [pad]
+  6 = ["a", "rightshoulder"]
+
+  [roms.9c2b3b9fd1a7e2a4]
+  name = "TETRIS"
+  keys = { 4 = ["Left"], 6 = ["Right"] }
+  pad = { 4 = ["dpleft", "leftx-"], 6 = ["dpright", "leftx+"] }
+*/
+
+// the usual mapping of the COSMAC VIP hex keypad onto a QWERTY keyboard:
+//   1 2 3 C      1 2 3 4
+//   4 5 6 D  ->  Q W E R
+//   7 8 9 E      A S D F
+//   A 0 B F      Z X C V
+const DEFAULT_LAYOUT: [(usize, &str); 16] = [
+    (0x1, "1"),
+    (0x2, "2"),
+    (0x3, "3"),
+    (0xC, "4"),
+    (0x4, "Q"),
+    (0x5, "W"),
+    (0x6, "E"),
+    (0xD, "R"),
+    (0x7, "A"),
+    (0x8, "S"),
+    (0x9, "D"),
+    (0xE, "F"),
+    (0xA, "Z"),
+    (0x0, "X"),
+    (0xB, "C"),
+    (0xF, "V"),
+];
+
+/// Anything on the host that can hold a CHIP-8 key down.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HostInput {
+    Key(Keycode),
+    // controller instance id and the input on it
+    Pad(u32, PadInput),
+}
+
+/// CHIP-8 key (as a hex digit) to the names of the SDL keys bound to it.
+pub type KeyTable = BTreeMap<String, Vec<String>>;
+
+/// The bindings file, see the format above.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct KeyConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadzone: Option<f32>,
+    #[serde(default)]
+    pub keys: KeyTable,
+    #[serde(default, skip_serializing_if = "KeyTable::is_empty")]
+    pub pad: KeyTable,
+    #[serde(default)]
+    pub roms: BTreeMap<String, RomKeys>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct RomKeys {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub keys: KeyTable,
+    #[serde(default, skip_serializing_if = "KeyTable::is_empty")]
+    pub pad: KeyTable,
+}
+
+impl KeyConfig {
+    /// Reads the bindings file. A missing file is an empty config.
+    pub fn load(path: &Path) -> Result<KeyConfig, String> {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(KeyConfig::default()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = toml::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| e.to_string())
+    }
+}
+
+/// How `roms` tables are keyed.
+pub fn rom_key(rom_hash: u64) -> String {
+    format!("{:016x}", rom_hash)
+}
+
+/// Host keys to CHIP-8 keys, several host keys can share a CHIP-8 key.
+pub struct KeyBindings {
+    keys: HashMap<Keycode, usize>,
+}
+
+impl KeyBindings {
+    /// The built-in layout, overridden by the config's global table and
+    /// then by the table for this ROM, if it has one.
+    pub fn from_config(config: &KeyConfig, rom_hash: u64) -> Result<KeyBindings, String> {
+        let mut bindings = KeyBindings::default();
+        bindings.apply(&config.keys)?;
+        if let Some(rom) = config.roms.get(&rom_key(rom_hash)) {
+            bindings.apply(&rom.keys)?;
+        }
+        Ok(bindings)
+    }
+
+    pub fn get(&self, key: Keycode) -> Option<usize> {
+        self.keys.get(&key).copied()
+    }
+
+    /// The host keys bound to a CHIP-8 key, by name.
+    pub fn names(&self, chip8_key: usize) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .keys
+            .iter()
+            .filter(|(_, &bound)| bound == chip8_key)
+            .map(|(key, _)| key.name())
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn apply(&mut self, table: &KeyTable) -> Result<(), String> {
+        apply_table(&mut self.keys, table, |name| {
+            Keycode::from_name(name).ok_or_else(|| format!("Unknown key name '{}'", name))
+        })
+    }
+}
+
+/// Layers `table` over `bindings`. Each CHIP-8 key in the table loses its
+/// old inputs, and inputs move over from whatever they were bound to before.
+pub fn apply_table<T: Eq + Hash>(
+    bindings: &mut HashMap<T, usize>,
+    table: &KeyTable,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<(), String> {
+    for (chip8_key, names) in table {
+        let chip8_key = usize::from_str_radix(chip8_key, 16)
+            .ok()
+            .filter(|&key| key <= 0xF)
+            .ok_or_else(|| format!("'{}' is not a CHIP-8 key 0-F", chip8_key))?;
+        bindings.retain(|_, bound| *bound != chip8_key);
+        for name in names {
+            bindings.insert(parse(name)?, chip8_key);
+        }
+    }
+    Ok(())
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let keys = DEFAULT_LAYOUT
+            .iter()
+            .map(|&(chip8_key, name)| (Keycode::from_name(name).unwrap(), chip8_key))
+            .collect();
+        KeyBindings { keys }
+    }
+}
+
+/// The host inputs holding down each CHIP-8 key. The keyboard and every
+/// controller go through the same one, so a key bound to several inputs
+/// only goes up once the last of them is let go.
+pub struct HeldKeys<S> {
+    held: [Vec<S>; KEYBOARD_MAP_SIZE],
+}
+
+impl<S: PartialEq> HeldKeys<S> {
+    pub fn new() -> Self {
+        HeldKeys {
+            held: Default::default(),
+        }
+    }
+
+    /// Records `source` pressing `key`. True if that presses the key, false
+    /// if something was already holding it. A source holds one key at a
+    /// time, pressing it again before it's let go does nothing.
+    pub fn press(&mut self, key: usize, source: S) -> bool {
+        if self.held.iter().any(|sources| sources.contains(&source)) {
+            return false;
+        }
+        let sources = &mut self.held[key];
+        sources.push(source);
+        sources.len() == 1
+    }
+
+    /// Records `source` letting go of the key it pressed, even if it has
+    /// been rebound since. That key, if nothing else is still holding it.
+    pub fn release(&mut self, source: &S) -> Option<usize> {
+        self.held.iter_mut().enumerate().find_map(|(key, sources)| {
+            let idx = sources.iter().position(|held| held == source)?;
+            sources.swap_remove(idx);
+            sources.is_empty().then_some(key)
+        })
+    }
+
+    /// Lets go of everything `matches` picks out, like all the inputs on an
+    /// unplugged controller. The keys that released.
+    pub fn release_where(&mut self, matches: impl Fn(&S) -> bool) -> Vec<usize> {
+        let mut released = Vec::new();
+        for (key, sources) in self.held.iter_mut().enumerate() {
+            let was_held = !sources.is_empty();
+            sources.retain(|source| !matches(source));
+            if was_held && sources.is_empty() {
+                released.push(key);
+            }
+        }
+        released
+    }
+}
+
+impl<S: PartialEq> Default for HeldKeys<S> {
+    fn default() -> Self {
+        HeldKeys::new()
+    }
+}
+
+/// The rebind screen: asks for a host key for each CHIP-8 key in keypad
+/// order, then writes them to the global table or to one ROM's table.
+pub struct Rebind {
+    rom: Option<String>,
+    next: usize,
+    chosen: Vec<(usize, Keycode)>,
+}
+
+impl Rebind {
+    /// Rebinds the keys for the ROM keyed `rom`, or the global ones.
+    pub fn new(rom: Option<String>) -> Self {
+        Rebind {
+            rom,
+            next: 0,
+            chosen: Vec::new(),
+        }
+    }
+
+    /// The CHIP-8 key waiting for a binding.
+    pub fn current(&self) -> usize {
+        DEFAULT_LAYOUT[self.next].0
+    }
+
+    pub fn prompt(&self, bindings: &KeyBindings) -> String {
+        let scope = if self.rom.is_some() { "ROM" } else { "ALL" };
+        format!(
+            "{} KEY {:X}: PRESS A KEY, ENTER KEEPS {}",
+            scope,
+            self.current(),
+            bindings.names(self.current()).join(" ")
+        )
+    }
+
+    /// Binds the current CHIP-8 key to `key`, or keeps its bindings when
+    /// `key` is `None`. True once every key has had its turn.
+    pub fn press(&mut self, key: Option<Keycode>) -> bool {
+        if let Some(key) = key {
+            self.chosen.push((self.current(), key));
+        }
+        self.next += 1;
+        self.next == DEFAULT_LAYOUT.len()
+    }
+
+    /// Writes the chosen keys into `config`.
+    pub fn finish(self, config: &mut KeyConfig, rom_name: &str) {
+        let table = match self.rom {
+            Some(rom) => {
+                let entry = config.roms.entry(rom).or_default();
+                entry.name = Some(rom_name.to_string());
+                &mut entry.keys
+            }
+            None => &mut config.keys,
+        };
+        for (chip8_key, key) in self.chosen {
+            table.insert(format!("{:X}", chip8_key), vec![key.name()]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HeldKeys;
+
+    #[test]
+    fn held_keys_release_with_the_last_input() {
+        let mut held = HeldKeys::new();
+
+        assert!(held.press(0x5, "W"));
+        assert!(!held.press(0x5, "Up"));
+        // key repeat
+        assert!(!held.press(0x5, "W"));
+
+        assert_eq!(held.release(&"W"), None);
+        assert_eq!(held.release(&"Up"), Some(0x5));
+        // already up
+        assert_eq!(held.release(&"Up"), None);
+        assert!(held.press(0x5, "Up"));
+    }
+
+    #[test]
+    fn held_keys_shared_between_devices() {
+        // (controller, input), with controller 0 standing in for the keyboard
+        let mut held = HeldKeys::new();
+        assert!(held.press(0x7, (1, "dpleft")));
+        assert!(!held.press(0x7, (1, "leftx-")));
+        assert!(!held.press(0x7, (0, "A")));
+
+        // the stick and keyboard are still pushed
+        assert_eq!(held.release(&(1, "dpleft")), None);
+        assert_eq!(held.release(&(0, "A")), None);
+        assert_eq!(held.release(&(1, "leftx-")), Some(0x7));
 
-pub fn key_btn_mapper(key: Keycode) -> Option<usize> {
-    match key {
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Num4 => Some(0xC),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None,
+        assert!(held.press(0x7, (1, "dpleft")));
+        assert!(held.press(0x9, (2, "dpright")));
+        assert!(!held.press(0x9, (0, "D")));
+        // unplugging controller 2 leaves 9 to the keyboard
+        assert_eq!(
+            held.release_where(|&(pad, _)| pad == 2),
+            Vec::<usize>::new()
+        );
+        assert_eq!(held.release_where(|&(pad, _)| pad == 1), vec![0x7]);
+        assert_eq!(held.release(&(0, "D")), Some(0x9));
     }
 }
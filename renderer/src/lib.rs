@@ -1,3 +1,8 @@
+pub mod audio;
+pub mod cli;
+pub mod gamepad;
 pub mod init;
 pub mod input;
+pub mod overlay;
 pub mod renderer;
+pub mod text;
@@ -1,69 +1,254 @@
 use chip8::chip8::Chip8;
 use chip8::constants::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use chip8::mode::Mode;
+use chip8::movie::{fnv1a, Movie, MoviePlayer, MovieRecorder};
+use chip8::rewind::Rewind;
+use chip8::scheduler::Scheduler;
+use renderer::audio::Audio;
+use renderer::cli::{parse_args, pick_rom, USAGE};
+use renderer::gamepad::{Gamepads, PadBindings};
 use renderer::init::{init_sdl, InitSdlReturn};
+use renderer::input::{rom_key, HeldKeys, HostInput, KeyBindings, KeyConfig, Rebind};
+use renderer::overlay::PANEL_WIDTH;
 
-use std::fs::{self, File};
-use std::io::{self, Read};
-use std::path::Path;
+use std::env;
+use std::fs;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Mod};
 
-const SCALE: u32 = 15;
-const WINDOW_WIDTH: u32 = (DISPLAY_WIDTH as u32) * SCALE;
-const WINDOW_HEIGHT: u32 = (DISPLAY_HEIGHT as u32) * SCALE;
-const CYCLE: usize = 10;
-
-const ROMS_DIR: &str = "../ROMs/";
+const STATE_SLOTS: u8 = 10;
+const SLOW_MOTION: f64 = 0.25;
+// +/- change the speed by one instruction a frame
+const IPS_STEP: u64 = 60;
+const NOTIFICATION_TIME: Duration = Duration::from_secs(2);
 
 fn main() {
-    let roms_dir = Path::new(ROMS_DIR);
-    let rom_files: Vec<_> = match fs::read_dir(roms_dir) {
-        Ok(entries) => entries
-            .filter_map(Result::ok)
-            .filter(|entry| entry.path().is_file())
-            .collect(),
-        Err(_) => {
-            println!("Could not open the ROMs directory.");
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let mut options = parse_args(args.into_iter()).unwrap_or_else(|e| {
+        println!("{}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+
+    let rom_path = match options.rom.clone().map_or_else(pick_rom, Ok) {
+        Ok(path) => path,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let buffer = match fs::read(&rom_path) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            println!("Could not read {}: {}", rom_path.display(), e);
             return;
         }
     };
 
-    println!("Available ROMs:");
-    for (index, entry) in rom_files.iter().enumerate() {
-        println!("{}: {}", index + 1, entry.file_name().to_string_lossy());
+    let rom_hash = fnv1a(&buffer);
+    let rom_name = rom_path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let mut key_config = match KeyConfig::load(&options.keys) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("Could not read {}: {}", options.keys.display(), e);
+            return;
+        }
+    };
+    let bindings = KeyBindings::from_config(&key_config, rom_hash)
+        .and_then(|keys| Ok((keys, PadBindings::from_config(&key_config, rom_hash)?)));
+    let (mut bindings, pad_bindings) = match bindings {
+        Ok(bindings) => bindings,
+        Err(e) => {
+            println!("Invalid key bindings in {}: {}", options.keys.display(), e);
+            return;
+        }
+    };
+
+    // a movie replays on the machine it was recorded on
+    let mut player = None;
+    if let Some(path) = options.play.as_deref() {
+        let movie = match fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|data| Movie::from_bytes(&data).map_err(|e| e.to_string()))
+            .and_then(|movie| {
+                movie
+                    .check_rom(&buffer)
+                    .map(|_| movie)
+                    .map_err(|e| e.to_string())
+            }) {
+            Ok(movie) => movie,
+            Err(e) => {
+                println!("Could not play {}: {}", path.display(), e);
+                return;
+            }
+        };
+        let header = &movie.header;
+        options.quirks = header.quirks;
+        options.mode = Some(header.mode);
+        options.seed = Some(header.seed);
+        options.font_base = Some(header.font_base);
+        options.ips = header.ips;
+        player = Some(MoviePlayer::new(movie));
     }
 
-    println!("Enter the number of the ROM to load:");
-    let mut input = String::new();
-    io::stdin()
-        .read_line(&mut input)
-        .expect("Failed to read input");
-    let choice: usize = match input.trim().parse::<usize>() {
-        Ok(num) if num > 0 && num <= rom_files.len() => num - 1,
-        _ => {
-            println!("Invalid selection. Exiting.");
+    // XO-CHIP ROMs are conventionally distributed as .xo8
+    let mode = options
+        .mode
+        .unwrap_or(match rom_path.extension().and_then(|ext| ext.to_str()) {
+            Some("xo8") => Mode::XoChip,
+            _ => Mode::Classic,
+        });
+
+    let mut chip8 = Chip8::new(options.quirks).with_mode(mode);
+    if let Some(seed) = options.seed {
+        chip8 = chip8.with_seed(seed);
+    }
+    if let Some(addr) = options.font_base {
+        chip8 = match chip8.try_with_font_base(addr) {
+            Ok(chip8) => chip8,
+            Err(e) => {
+                println!("{}", e);
+                return;
+            }
+        };
+    }
+    chip8.load_fonts();
+    if let Some(path) = options.font.as_deref() {
+        let loaded = fs::read(path)
+            .map_err(|e| e.to_string())
+            .and_then(|font| chip8.load_custom_font(&font).map_err(|e| e.to_string()));
+        if let Err(e) = loaded {
+            println!("Could not load font {}: {}", path.display(), e);
             return;
         }
-    };
+    }
+    if let Err(e) = chip8.load_rom(&buffer) {
+        println!("Could not load ROM: {}", e);
+        return;
+    }
 
-    let init: InitSdlReturn = init_sdl(WINDOW_WIDTH, WINDOW_HEIGHT);
+    let init: InitSdlReturn = init_sdl(
+        DISPLAY_WIDTH as u32 * options.scale,
+        DISPLAY_HEIGHT as u32 * options.scale,
+        options.vsync,
+        options.fullscreen,
+    );
     let sdl_context = init.sdl_context;
     let mut canvas = init.canvas;
     let mut event_pump = sdl_context.event_pump().unwrap();
+    // carry on silently if there's no audio device
+    let mut audio = match Audio::new(&sdl_context, options.audio) {
+        Ok(mut audio) => {
+            audio.muted = options.mute;
+            Some(audio)
+        }
+        Err(e) => {
+            println!("Could not open audio: {}", e);
+            None
+        }
+    };
 
-    let mut chip8 = Chip8::new();
-    chip8.load_fonts();
+    // controllers are optional, like audio
+    let mut gamepads = match Gamepads::new(&sdl_context, pad_bindings) {
+        Ok(gamepads) => Some(gamepads),
+        Err(e) => {
+            println!("Could not start controller support: {}", e);
+            None
+        }
+    };
+
+    let mut recorder = options
+        .record
+        .as_ref()
+        .map(|_| MovieRecorder::new(&chip8, &buffer, options.ips));
+    // rewinding, loading states and changing the IPS would all throw the
+    // movie off, so they wait until it's done
+    let mut in_movie = recorder.is_some() || player.is_some();
+
+    // SUPER-CHIP RPL user flags persist between runs, next to the ROM. Movies
+    // start without them so they play back the same on any machine
+    let rpl_path = rom_path.with_extension("rpl");
+    if let Some(flags) = fs::read(&rpl_path).ok().filter(|_| !in_movie) {
+        let n = flags.len().min(chip8.rpl.len());
+        chip8.rpl[..n].copy_from_slice(&flags[..n]);
+    }
 
-    let rom_path = rom_files[choice].path();
-    let mut rom = File::open(&rom_path).expect("Unable to open ROM file");
-    let mut buffer = Vec::new();
-    rom.read_to_end(&mut buffer).unwrap();
-    chip8.load_rom(&buffer);
+    // set once the ROM faults; the last frame stays up until the window closes
+    let mut halted = false;
+    // F5 saves to and F7 loads from <rom>.st<slot>, F6 picks the next slot
+    let mut slot: u8 = 0;
+    let mut scheduler = Scheduler::new(options.ips);
+    let mut last_frame = Instant::now();
+    // F1 shows the debug panel, widening the window to make room for it
+    let mut overlay = false;
+    // holding Backspace plays the recent past backwards, one frame per frame
+    let mut rewind = Rewind::new(options.rewind_frames, options.rewind_bytes);
+    let mut rewinding = false;
+    // Tab held runs at turbo speed, F2 toggles slow motion, P pauses and N
+    // runs a single frame while paused
+    let mut turbo = false;
+    let mut slow_motion = false;
+    let mut paused = false;
+    let mut step_frame = false;
+    // shown over the game for a moment after a speed change
+    let mut notification: Option<(String, Instant)> = None;
+    // F8 walks through the keypad rebinding keys for this ROM, Shift+F8 for
+    // all of them. The game waits meanwhile
+    let mut rebind: Option<Rebind> = None;
+    // a CHIP-8 key bound to several host keys or controller inputs stays
+    // down until the last of them comes up
+    let mut held = HeldKeys::new();
 
     'execloop: loop {
         for evt in event_pump.poll_iter() {
+            if let Some(gamepads) = gamepads.as_mut() {
+                for (k, pressed) in gamepads.handle(&evt, &mut held) {
+                    press(&mut chip8, recorder.as_mut(), player.is_some(), k, pressed);
+                }
+            }
             match evt {
+                Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } if rebind.is_some() => {
+                    rebind = None;
+                    notification = Some(("KEYS UNCHANGED".to_string(), Instant::now()));
+                }
+                Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } if rebind.is_some() => {
+                    let keep = key == Keycode::Return || key == Keycode::KpEnter;
+                    if rebind.as_mut().unwrap().press((!keep).then_some(key)) {
+                        rebind.take().unwrap().finish(&mut key_config, &rom_name);
+                        let saved = key_config
+                            .save(&options.keys)
+                            .and_then(|_| KeyBindings::from_config(&key_config, rom_hash));
+                        let text = match saved {
+                            Ok(new_bindings) => {
+                                bindings = new_bindings;
+                                "KEYS SAVED"
+                            }
+                            Err(e) => {
+                                println!("Could not save key bindings: {}", e);
+                                "KEYS NOT SAVED"
+                            }
+                        };
+                        notification = Some((text.to_string(), Instant::now()));
+                    }
+                }
                 Event::Quit { .. }
                 | Event::KeyDown {
                     keycode: Some(Keycode::Escape),
@@ -71,28 +256,289 @@ fn main() {
                 } => {
                     break 'execloop;
                 }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F1),
+                    ..
+                } => {
+                    overlay = !overlay;
+                    if !options.fullscreen {
+                        let window = canvas.window_mut();
+                        let (w, h) = window.size();
+                        let w = if overlay {
+                            w + PANEL_WIDTH
+                        } else {
+                            w - PANEL_WIDTH
+                        };
+                        window.set_size(w, h).unwrap();
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::M),
+                    ..
+                } => {
+                    if let Some(audio) = audio.as_mut() {
+                        audio.toggle_mute();
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F5),
+                    ..
+                } => {
+                    let path = rom_path.with_extension(format!("st{}", slot));
+                    match fs::write(&path, chip8.save_state()) {
+                        Ok(()) => println!("Saved state to slot {}", slot),
+                        Err(e) => println!("Could not save state: {}", e),
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F6),
+                    ..
+                } => {
+                    slot = (slot + 1) % STATE_SLOTS;
+                    println!("Save state slot {}", slot);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    ..
+                } if in_movie => {
+                    println!("Can't load states while a movie is recording or playing")
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F7),
+                    ..
+                } => {
+                    let path = rom_path.with_extension(format!("st{}", slot));
+                    // keys stay as the player holds them, like when rewinding
+                    let keys = chip8.keyboard;
+                    match fs::read(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|state| chip8.load_state(&state).map_err(|e| e.to_string()))
+                    {
+                        Ok(()) => {
+                            println!("Loaded state from slot {}", slot);
+                            chip8.keyboard = keys;
+                            halted = false;
+                            // the history led up to the state just replaced
+                            rewind.clear();
+                        }
+                        Err(e) => println!("Could not load state: {}", e),
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F8),
+                    keymod,
+                    repeat: false,
+                    ..
+                } => {
+                    let global = keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD);
+                    rebind = Some(Rebind::new((!global).then(|| rom_key(rom_hash))));
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = options.rewind_frames > 0 && !in_movie,
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = false,
+                Event::KeyDown {
+                    keycode: Some(Keycode::Tab),
+                    repeat: false,
+                    ..
+                } => {
+                    turbo = true;
+                    let text = match options.turbo {
+                        0 => "TURBO".to_string(),
+                        n => format!("TURBO {}X", n),
+                    };
+                    notification = Some((text, Instant::now()));
+                }
+                Event::KeyUp {
+                    keycode: Some(Keycode::Tab),
+                    ..
+                } => turbo = false,
+                Event::KeyDown {
+                    keycode: Some(Keycode::F2),
+                    repeat: false,
+                    ..
+                } => {
+                    slow_motion = !slow_motion;
+                    let text = if slow_motion {
+                        "SLOW MOTION"
+                    } else {
+                        "NORMAL SPEED"
+                    };
+                    notification = Some((text.to_string(), Instant::now()));
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::P),
+                    repeat: false,
+                    ..
+                } => {
+                    paused = !paused;
+                    let text = if paused { "PAUSED" } else { "RESUMED" };
+                    notification = Some((text.to_string(), Instant::now()));
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::N),
+                    ..
+                } => step_frame = paused,
+                Event::KeyDown {
+                    keycode:
+                        Some(
+                            key @ (Keycode::Equals
+                            | Keycode::KpPlus
+                            | Keycode::Minus
+                            | Keycode::KpMinus),
+                        ),
+                    ..
+                } => {
+                    // the movie's timing depends on it
+                    let text = if in_movie {
+                        "IPS FIXED BY THE MOVIE".to_string()
+                    } else {
+                        let ips = match key {
+                            Keycode::Minus | Keycode::KpMinus => {
+                                scheduler.ips().saturating_sub(IPS_STEP).max(IPS_STEP)
+                            }
+                            _ => scheduler.ips() + IPS_STEP,
+                        };
+                        scheduler.set_ips(ips);
+                        format!("IPS {}", ips)
+                    };
+                    notification = Some((text, Instant::now()));
+                }
                 Event::KeyDown {
                     keycode: Some(key), ..
                 } => {
-                    if let Some(k) = renderer::input::key_btn_mapper(key) {
-                        chip8.keypress(k, true);
+                    if let Some(k) = bindings
+                        .get(key)
+                        .filter(|&k| held.press(k, HostInput::Key(key)))
+                    {
+                        press(&mut chip8, recorder.as_mut(), player.is_some(), k, true);
                     }
                 }
                 Event::KeyUp {
                     keycode: Some(key), ..
                 } => {
-                    if let Some(k) = renderer::input::key_btn_mapper(key) {
-                        chip8.keypress(k, false);
+                    if let Some(k) = held.release(&HostInput::Key(key)) {
+                        press(&mut chip8, recorder.as_mut(), player.is_some(), k, false);
                     }
                 }
                 _ => (),
             }
         }
 
-        for _ in 0..CYCLE {
-            chip8.tick();
+        if chip8.exited {
+            break 'execloop;
+        }
+
+        scheduler.set_speed(match (turbo, slow_motion) {
+            (true, _) => options.turbo.max(1) as f64,
+            (false, true) => SLOW_MOTION,
+            (false, false) => 1.0,
+        });
+        // unlimited turbo runs frames until the display is due a new one
+        let unlimited = turbo && options.turbo == 0;
+        let now = Instant::now();
+        let due = scheduler.advance(now - last_frame);
+        last_frame = now;
+        let due = if rebind.is_some() {
+            0
+        } else if paused {
+            std::mem::take(&mut step_frame) as u32
+        } else if unlimited {
+            u32::MAX
+        } else {
+            due
+        };
+        for _ in 0..due {
+            if unlimited && now.elapsed() >= Scheduler::frame_time() {
+                break;
+            }
+            if rewinding {
+                // keys stay as the player holds them, and rewinding past a
+                // fault lets them carry on from before it
+                let keys = chip8.keyboard;
+                if rewind.step_back(&mut chip8) {
+                    chip8.keyboard = keys;
+                    halted = false;
+                }
+                continue;
+            }
+            if halted || chip8.exited {
+                break;
+            }
+            if let Some(Err(e)) = player.as_mut().map(|player| player.begin_frame(&mut chip8)) {
+                println!("Movie stopped: {}", e);
+                player = None;
+                in_movie = recorder.is_some();
+            }
+            if let Err(e) = scheduler.run_frame(&mut chip8) {
+                println!("Emulation halted: {}", e);
+                halted = true;
+            }
+            if options.rewind_frames > 0 && !in_movie {
+                rewind.push(&chip8);
+            }
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.end_frame(&chip8);
+            }
+            if let Some(movie) = player.as_mut() {
+                match movie.end_frame(&chip8) {
+                    Err(e) => println!("Movie stopped: {}", e),
+                    Ok(()) if movie.is_finished() => {
+                        println!("Movie finished after {} frames", movie.frame())
+                    }
+                    Ok(()) => continue,
+                }
+                player = None;
+                in_movie = recorder.is_some();
+            }
+        }
+        if let Some(audio) = audio.as_mut() {
+            audio.update(chip8.is_sound_active() && !halted && !rewinding && !paused);
+        }
+        let prompt = rebind.as_ref().map(|rebind| rebind.prompt(&bindings));
+        let message = prompt.as_deref().or_else(|| {
+            notification
+                .as_ref()
+                .filter(|(_, shown)| shown.elapsed() < NOTIFICATION_TIME)
+                .map(|(text, _)| text.as_str())
+        });
+        renderer::renderer::draw_screen(&chip8, &mut canvas, &options.palette, overlay, message);
+        if due == 0 && !options.vsync {
+            thread::sleep(scheduler.until_next_frame());
+        }
+    }
+
+    if let (Some(recorder), Some(path)) = (recorder, options.record.as_deref()) {
+        match fs::write(path, recorder.finish(&chip8).to_bytes()) {
+            Ok(()) => println!("Recorded a movie to {}", path.display()),
+            Err(e) => println!("Could not write {}: {}", path.display(), e),
+        }
+    }
+    if chip8.rpl.iter().any(|&flag| flag != 0) {
+        if let Err(e) = fs::write(&rpl_path, chip8.rpl) {
+            println!("Could not save RPL flags: {}", e);
         }
-        chip8.tick_timers();
-        renderer::renderer::draw_screen(&chip8, &mut canvas);
+    }
+}
+
+// keys go through the movie recorder when there is one, and are ignored
+// while a movie plays
+fn press(
+    chip8: &mut Chip8,
+    recorder: Option<&mut MovieRecorder>,
+    playing: bool,
+    key: usize,
+    pressed: bool,
+) {
+    if playing {
+        return;
+    }
+    match recorder {
+        Some(recorder) => recorder.keypress(chip8, key, pressed).unwrap(),
+        None => chip8.keypress(key, pressed).unwrap(),
     }
 }
@@ -0,0 +1,119 @@
+use chip8::chip8::Chip8;
+use chip8::disasm::{disassemble_linear, Syntax};
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+use crate::text::{draw_text, GLYPH_HEIGHT, GLYPH_WIDTH};
+
+// room for 40 characters at TEXT_SCALE
+pub const PANEL_WIDTH: u32 = 320;
+const TEXT_SCALE: u32 = 2;
+const MARGIN: i32 = 8;
+
+const BACKGROUND: Color = Color::RGB(24, 24, 32);
+const TEXT: Color = Color::RGB(200, 200, 200);
+const HIGHLIGHT: Color = Color::RGB(255, 200, 0);
+const NOTIFICATION_SCALE: u32 = 3;
+
+/// The register half of the panel, one string per line.
+pub fn state_lines(chip8: &Chip8) -> Vec<String> {
+    let mut lines = vec![
+        format!(
+            "PC {:03X}  I {:03X}  SP {:X}",
+            chip8.program_counter, chip8.ireg, chip8.stack_pointer
+        ),
+        format!("DT {:02X}  ST {:02X}", chip8.delay_timer, chip8.sound_timer),
+        String::new(),
+    ];
+    for (row, values) in chip8.vregs.chunks(4).enumerate() {
+        let regs: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(col, value)| format!("V{:X} {:02X}", row * 4 + col, value))
+            .collect();
+        lines.push(regs.join("  "));
+    }
+    lines.push(String::new());
+
+    let stack: Vec<String> = chip8.stack[..chip8.stack_pointer as usize]
+        .iter()
+        .rev()
+        .map(|addr| format!("{:03X}", addr))
+        .collect();
+    lines.push(format!("STACK {}", stack.join(" ")));
+
+    let keys: Vec<String> = (0..chip8.keyboard.len())
+        .filter(|&key| chip8.keyboard[key])
+        .map(|key| format!("{:X}", key))
+        .collect();
+    lines.push(format!("KEYS  {}", keys.join(" ")));
+    lines.push(String::new());
+    lines
+}
+
+/// Disassembly starting a few instructions before PC. Code before PC can't
+/// be told apart from data, so this simply assumes it's instructions.
+fn disassembly(chip8: &Chip8, count: usize) -> Vec<(bool, String)> {
+    let pc = chip8.program_counter as usize;
+    let start = pc.saturating_sub(2 * (count / 3));
+    let end = (start + 4 * count).min(chip8.ram.len());
+    disassemble_linear(&chip8.ram[start.min(end)..end], start as u16)
+        .iter()
+        .take(count)
+        .map(|line| {
+            let current = line.addr() as usize == pc;
+            let marker = if current { ">" } else { " " };
+            (
+                current,
+                format!("{}{}", marker, line.format(Syntax::Cowgod)),
+            )
+        })
+        .collect()
+}
+
+/// Fills `area` with the machine state and a disassembly around PC.
+pub fn draw_panel(chip8: &Chip8, canvas: &mut Canvas<Window>, area: Rect) {
+    canvas.set_draw_color(BACKGROUND);
+    canvas.fill_rect(area).unwrap();
+
+    let line_height = (GLYPH_HEIGHT * TEXT_SCALE) as i32 + 2;
+    let max_chars =
+        (area.width() as i32 - 2 * MARGIN) as usize / (GLYPH_WIDTH * TEXT_SCALE) as usize;
+    let mut y = area.y() + MARGIN;
+
+    for line in state_lines(chip8) {
+        draw_text(canvas, &line, area.x() + MARGIN, y, TEXT_SCALE, TEXT);
+        y += line_height;
+    }
+
+    let room = (area.bottom() - MARGIN - y) / line_height;
+    for (current, line) in disassembly(chip8, room.max(0) as usize) {
+        let line: String = line.chars().take(max_chars).collect();
+        let color = if current { HIGHLIGHT } else { TEXT };
+        draw_text(canvas, &line, area.x() + MARGIN, y, TEXT_SCALE, color);
+        y += line_height;
+    }
+}
+
+/// A short message in the top left corner of `area`, on a dark box so it
+/// reads over any palette.
+pub fn draw_notification(canvas: &mut Canvas<Window>, text: &str, area: Rect) {
+    let width = text.chars().count() as u32 * GLYPH_WIDTH * NOTIFICATION_SCALE;
+    let height = GLYPH_HEIGHT * NOTIFICATION_SCALE;
+    let padding = NOTIFICATION_SCALE as i32 * 2;
+    let x = area.x() + MARGIN;
+    let y = area.y() + MARGIN;
+
+    canvas.set_draw_color(BACKGROUND);
+    canvas
+        .fill_rect(Rect::new(
+            x - padding,
+            y - padding,
+            width + 2 * padding as u32,
+            height + 2 * padding as u32,
+        ))
+        .unwrap();
+    draw_text(canvas, text, x, y, NOTIFICATION_SCALE, HIGHLIGHT);
+}
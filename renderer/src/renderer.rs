@@ -1,25 +1,81 @@
-use chip8::constants::DISPLAY_WIDTH;
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use sdl2::render::Canvas;
 use sdl2::video::Window;
 
-const SCALE: u32 = 15;
+use crate::overlay::{draw_notification, draw_panel, PANEL_WIDTH};
 
-pub fn draw_screen(emulator: &chip8::chip8::Chip8, canvas: &mut Canvas<Window>) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
+// colors for each combination of lit XO-CHIP planes: none, plane 1, plane 2,
+// both. classic ROMs only ever use the first two
+pub const DEFAULT_PALETTE: [Color; 4] = [
+    Color::RGB(0, 0, 0),
+    Color::RGB(255, 255, 255),
+    Color::RGB(170, 170, 170),
+    Color::RGB(85, 85, 85),
+];
+
+pub fn palette_from_name(name: &str) -> Option<[Color; 4]> {
+    match name.to_ascii_lowercase().as_str() {
+        "mono" => Some(DEFAULT_PALETTE),
+        "amber" => Some([
+            Color::RGB(20, 10, 0),
+            Color::RGB(255, 176, 0),
+            Color::RGB(170, 100, 0),
+            Color::RGB(255, 220, 140),
+        ]),
+        "green" => Some([
+            Color::RGB(0, 16, 0),
+            Color::RGB(51, 255, 51),
+            Color::RGB(0, 140, 0),
+            Color::RGB(170, 255, 170),
+        ]),
+        // the four shades of the original Game Boy screen
+        "lcd" => Some([
+            Color::RGB(155, 188, 15),
+            Color::RGB(15, 56, 15),
+            Color::RGB(48, 98, 48),
+            Color::RGB(139, 172, 15),
+        ]),
+        _ => None,
+    }
+}
+
+/// Draws the framebuffer, and the debug panel on the right when `overlay`
+/// is set, with `notification` over the game, then presents the frame.
+pub fn draw_screen(
+    emulator: &chip8::chip8::Chip8,
+    canvas: &mut Canvas<Window>,
+    palette: &[Color; 4],
+    overlay: bool,
+    notification: Option<&str>,
+) {
+    canvas.set_draw_color(palette[0]);
     canvas.clear();
 
-    let screen_buf = emulator.get_display();
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
+    let (mut window_w, window_h) = canvas.output_size().unwrap();
+    if overlay {
+        let panel_w = PANEL_WIDTH.min(window_w);
+        window_w -= panel_w;
+        let area = Rect::new(window_w as i32, 0, panel_w, window_h);
+        draw_panel(emulator, canvas, area);
+    }
+
+    // scale the framebuffer to the window, whatever resolution the ROM picked
+    let (screen_buf, width, height) = emulator.get_display();
+    let scale = (window_w / width as u32).min(window_h / height as u32);
+
     for (i, pixel) in screen_buf.iter().enumerate() {
-        if *pixel {
-            let x = (i % DISPLAY_WIDTH) as u32;
-            let y = (i / DISPLAY_WIDTH) as u32;
+        if *pixel != 0 {
+            let x = (i % width) as u32;
+            let y = (i / width) as u32;
 
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
+            canvas.set_draw_color(palette[(*pixel & 0x3) as usize]);
+            let rect = Rect::new((x * scale) as i32, (y * scale) as i32, scale, scale);
             canvas.fill_rect(rect).unwrap();
         }
     }
+    if let Some(text) = notification {
+        draw_notification(canvas, text, Rect::new(0, 0, window_w, window_h));
+    }
     canvas.present();
 }
@@ -0,0 +1,111 @@
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+
+// glyphs are 3x5 pixels, with a pixel of spacing on the right and below
+pub const GLYPH_WIDTH: u32 = 4;
+pub const GLYPH_HEIGHT: u32 = 6;
+
+/*
+3x5 FONT (for reference):
+
+Each glyph is five rows of three bits, most significant bit on the left.
+Lowercase letters are drawn as uppercase and anything without a glyph as
+a question mark.
+
+"A"     Binary   Hex
+.#.     010      0x2
+#.#     101      0x5
+###     111      0x7
+#.#     101      0x5
+#.#     101      0x5
+*/
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0x7, 0x5, 0x5, 0x5, 0x7],
+        '1' => [0x2, 0x6, 0x2, 0x2, 0x7],
+        '2' => [0x7, 0x1, 0x7, 0x4, 0x7],
+        '3' => [0x7, 0x1, 0x7, 0x1, 0x7],
+        '4' => [0x5, 0x5, 0x7, 0x1, 0x1],
+        '5' => [0x7, 0x4, 0x7, 0x1, 0x7],
+        '6' => [0x7, 0x4, 0x7, 0x5, 0x7],
+        '7' => [0x7, 0x1, 0x1, 0x2, 0x2],
+        '8' => [0x7, 0x5, 0x7, 0x5, 0x7],
+        '9' => [0x7, 0x5, 0x7, 0x1, 0x7],
+        'A' => [0x2, 0x5, 0x7, 0x5, 0x5],
+        'B' => [0x6, 0x5, 0x6, 0x5, 0x6],
+        'C' => [0x3, 0x4, 0x4, 0x4, 0x3],
+        'D' => [0x6, 0x5, 0x5, 0x5, 0x6],
+        'E' => [0x7, 0x4, 0x6, 0x4, 0x7],
+        'F' => [0x7, 0x4, 0x6, 0x4, 0x4],
+        'G' => [0x3, 0x4, 0x5, 0x5, 0x3],
+        'H' => [0x5, 0x5, 0x7, 0x5, 0x5],
+        'I' => [0x7, 0x2, 0x2, 0x2, 0x7],
+        'J' => [0x1, 0x1, 0x1, 0x5, 0x2],
+        'K' => [0x5, 0x5, 0x6, 0x5, 0x5],
+        'L' => [0x4, 0x4, 0x4, 0x4, 0x7],
+        'M' => [0x5, 0x7, 0x7, 0x5, 0x5],
+        'N' => [0x6, 0x5, 0x5, 0x5, 0x5],
+        'O' => [0x2, 0x5, 0x5, 0x5, 0x2],
+        'P' => [0x6, 0x5, 0x6, 0x4, 0x4],
+        'Q' => [0x2, 0x5, 0x5, 0x6, 0x3],
+        'R' => [0x6, 0x5, 0x6, 0x5, 0x5],
+        'S' => [0x3, 0x4, 0x2, 0x1, 0x6],
+        'T' => [0x7, 0x2, 0x2, 0x2, 0x2],
+        'U' => [0x5, 0x5, 0x5, 0x5, 0x7],
+        'V' => [0x5, 0x5, 0x5, 0x5, 0x2],
+        'W' => [0x5, 0x5, 0x7, 0x7, 0x5],
+        'X' => [0x5, 0x5, 0x2, 0x5, 0x5],
+        'Y' => [0x5, 0x5, 0x2, 0x2, 0x2],
+        'Z' => [0x7, 0x1, 0x2, 0x4, 0x7],
+        ' ' => [0x0, 0x0, 0x0, 0x0, 0x0],
+        ',' => [0x0, 0x0, 0x0, 0x2, 0x4],
+        '.' => [0x0, 0x0, 0x0, 0x0, 0x2],
+        ':' => [0x0, 0x2, 0x0, 0x2, 0x0],
+        '[' => [0x6, 0x4, 0x4, 0x4, 0x6],
+        ']' => [0x3, 0x1, 0x1, 0x1, 0x3],
+        '(' => [0x2, 0x4, 0x4, 0x4, 0x2],
+        ')' => [0x2, 0x1, 0x1, 0x1, 0x2],
+        '#' => [0x5, 0x7, 0x5, 0x7, 0x5],
+        '-' => [0x0, 0x0, 0x7, 0x0, 0x0],
+        '+' => [0x0, 0x2, 0x7, 0x2, 0x0],
+        '=' => [0x0, 0x7, 0x0, 0x7, 0x0],
+        '<' => [0x1, 0x2, 0x4, 0x2, 0x1],
+        '>' => [0x4, 0x2, 0x1, 0x2, 0x4],
+        '_' => [0x0, 0x0, 0x0, 0x0, 0x7],
+        '/' => [0x1, 0x1, 0x2, 0x4, 0x4],
+        '*' => [0x0, 0x5, 0x2, 0x5, 0x0],
+        '!' => [0x2, 0x2, 0x2, 0x0, 0x2],
+        _ => [0x6, 0x1, 0x2, 0x0, 0x2],
+    }
+}
+
+/// Draws one line of text with its top left corner at (x, y). Each font
+/// pixel is `scale` window pixels square.
+pub fn draw_text(
+    canvas: &mut Canvas<Window>,
+    text: &str,
+    x: i32,
+    y: i32,
+    scale: u32,
+    color: Color,
+) {
+    canvas.set_draw_color(color);
+    for (col, c) in text.chars().enumerate() {
+        let left = x + (col as u32 * GLYPH_WIDTH * scale) as i32;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for bit in 0..3 {
+                if bits & (0x4 >> bit) != 0 {
+                    let rect = Rect::new(
+                        left + (bit * scale) as i32,
+                        y + (row as u32 * scale) as i32,
+                        scale,
+                        scale,
+                    );
+                    canvas.fill_rect(rect).unwrap();
+                }
+            }
+        }
+    }
+}